@@ -378,7 +378,7 @@
 
 use colored::Colorize;
 
-pub unsafe fn init_game_env() {
+unsafe fn load_engine_lib() -> libloading::Library {
     let mut exe_path = std::env::current_exe().expect("Failed to get executable path!!");
     let mut path = exe_path.parent().expect("Failed to get executable path!!");
     let mut path_str = path.to_str().expect("Failed to read executable path!!").to_string();
@@ -388,14 +388,45 @@ pub unsafe fn init_game_env() {
     let mut lib_path = path_str.to_owned() + "/libblack_ice_lib.so";
     #[cfg(target_os = "mac")]
     let mut lib_path = path_str.to_owned() + "/black_ice_lib.dynlib";
-    let lib = libloading::Library::new(lib_path).expect("Failed to load engine library!!");
+    libloading::Library::new(lib_path).expect("Failed to load engine library!!")
+}
+
+pub unsafe fn init_game_env() {
+    let lib = load_engine_lib();
     let game_init : libloading::Symbol<unsafe extern fn()> = lib.get(b"init_game_env\0").expect("Failed to get init_game_env function. Maybe the engine is corrupted?");
     game_init();
 }
 
+// black_ice_executable pack <asset folder> <output .pkg>
+pub unsafe fn build_asset_pack(args: &[String]) -> i32 {
+    if args.len() != 2 {
+        println!("{}", "Usage: black_ice_executable pack <asset folder> <output .pkg>".red());
+        return 1;
+    }
+    let lib = load_engine_lib();
+    let build : libloading::Symbol<unsafe fn(String, String) -> bool> = lib.get(b"build_asset_pack\0").expect("Failed to get build_asset_pack function. Maybe the engine is corrupted?");
+    if build(args[0].clone(), args[1].clone()) { 0 } else { 1 }
+}
+
+// black_ice_executable cook <asset folder> <output .pkg>
+pub unsafe fn cook_asset_pack(args: &[String]) -> i32 {
+    if args.len() != 2 {
+        println!("{}", "Usage: black_ice_executable cook <asset folder> <output .pkg>".red());
+        return 1;
+    }
+    let lib = load_engine_lib();
+    let cook : libloading::Symbol<unsafe fn(String, String) -> bool> = lib.get(b"cook_asset_pack\0").expect("Failed to get cook_asset_pack function. Maybe the engine is corrupted?");
+    if cook(args[0].clone(), args[1].clone()) { 0 } else { 1 }
+}
+
 // F:\Rust\Program 1\target\debug\black_ice_lib.dll
 fn main(){
+    let args = std::env::args().collect::<Vec<String>>();
     unsafe{
-        init_game_env();
+        match args.get(1).map(|a| {a.as_str()}) {
+            Some("pack") => std::process::exit(build_asset_pack(&args[2..])),
+            Some("cook") => std::process::exit(cook_asset_pack(&args[2..])),
+            _ => init_game_env(),
+        }
     }
-}
\ No newline at end of file
+}
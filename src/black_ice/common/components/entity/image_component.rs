@@ -60,8 +60,20 @@ impl Constructor<Image> for Image {
         let value = definition.get("texture").expect("Failed to Build Image");
         let path = value.as_str().expect("Failed to load Texture path!!");
         //println!("{}", path);
+        // sprites can use a region of an atlas (registered with AtlasBuilder::register) instead of their own texture,
+        // if the texture has been packed into it. Otherwise we load the texture like normal
+        let atlas = definition.get("atlas").and_then(|a| {a.as_str()}).filter(|a| {!a.is_empty()});
+        let from_atlas = atlas.and_then(|atlas| {
+            let p_render_sys = Env::get_render_sys();
+            let render_sys = p_render_sys.read();
+            let image = render_sys.find_image(atlas.clone()).ok();
+            if image.is_none() {
+                println!("No atlas called {} has been registered!!", atlas);
+            }
+            image
+        }).and_then(|image| {Texture::from_atlas(image, path.clone())});
         Some(ComponentRef_new(Self {
-            texture: AssetManager::load_asset(path),
+            texture: from_atlas.unwrap_or_else(|| {AssetManager::load_asset(path)}),
             p_Entity: entity.clone()
         }))
 
@@ -91,12 +103,14 @@ impl Image {
             (0, 2, 3)
         ];
 
+        // if we are in an atlas, we only want our part of it
+        let region = self.texture.get_region();
         let tex_cood = vec![
             [0.0, 0.0],
             [1.0, 0.0],
             [1.0, 1.0],
             [0.0, 1.0]
-        ];
+        ].into_iter().map(|uv| {region.map_uv(uv)}).collect::<Vec<[f32; 2]>>();
         
         
         unsafe{
@@ -64,18 +64,24 @@ impl Reflection for MeshComponent{
 impl Constructor<MeshComponent> for MeshComponent {
     unsafe fn construct(entity: EntityPtr, definition: &ConstructorDefinition) -> Option<ComponentRef<MeshComponent>> {
         let mut mesh: Arc<Mutex<Mesh>>;
-        if definition.get("file").is_none() || definition["file"].get("mesh_file_path").is_none() || definition["file"]["mesh_file_path"].as_str().unwrap().is_empty() {
+        let mut materials: HashMap<u32, Arc<Mutex<Material>>> = HashMap::new();
+        // the file can be a mesh file definition, or just the path to the mesh (e.g. "ASSET:assets/models/crate.gltf")
+        let mesh_file_path = definition.get("file")
+            .and_then(|file| {file.as_str().or_else(|| {file.get("mesh_file_path").and_then(|path| {path.as_str()})})})
+            .unwrap_or_default();
+        if mesh_file_path.is_empty() {
             mesh = Arc::new(Mutex::new(Mesh::new()));
         }
         else{
-            let mesh_file = MeshFile::construct(definition.get("file").expect("Corrupted Mesh File Definition!!").clone());
+            let mesh_file = MeshFile::from_path(mesh_file_path);
             mesh = Arc::new(Mutex::new(mesh_file.as_mesh()));
+            // the surfaces of a mesh file use the materials that came with it
+            materials = mesh_file.materials.clone();
         }
         let layer = definition.get("layer").expect("Failed to get layer!!").as_u32().unwrap();
 
         // load the materials
         let material_list = definition.get("materials").expect("Failed to get materials list").as_vector();
-        let mut materials: HashMap<u32, Arc<Mutex<Material>>> = HashMap::new();
         
         Some(ComponentRef_new(MeshComponent {
             mesh: mesh.clone(), 
@@ -136,33 +142,19 @@ impl MeshComponent {
                 ];
                 let surface = p_surface.lock();
                 let id = surface.id.clone();
+                // the renderer reads the vertex streams and indices out of the surface, so there's nothing to draw without them
+                let is_empty = surface.verts.is_empty() || surface.indices.is_empty();
                 drop(surface);
+                if is_empty {
+                    continue;
+                }
                 let (di,p_material) = (&self.materials).into_iter().find(|x| {*x.0 == id}).unwrap();
                 let material = p_material.lock();
                 for (name, (p_value, value_type)) in &material.shader_descriptor {
                     match value_type {
                         crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataHint::Uniform => {
-                            let value = p_value.lock();
-                            match value.clone(){
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::Integer(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::Boolean(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::UnsignedInteger(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::Float(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::Double(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::Vec3(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::Vec4(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::Vec2(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::IVec3(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::IVec4(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::IVec2(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::UVec3(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::UVec4(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::UVec2(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::DVec3(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::DVec4(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::DVec2(i) => todo!(),
-                                crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataType::Sampler2D(vec, _, _) => todo!(),
-                            }
+                            // material params (like the ones that come with mesh files) are sent along with the surface
+                            data.push(Data::Uniform(name.clone(), p_value.lock().clone()));
                         },
                         crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataHint::In => todo!(),
                         crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataHint::Out => todo!(),
@@ -170,6 +162,16 @@ impl MeshComponent {
                         crate::black_ice::common::engine::asset_types::shader_asset::ShaderDataHint::Buffer {layout} => todo!(),
                     }
                 }
+                // the textures go to the samplers with the same names, as the images that they were registered as
+                let p_render_sys = Env::get_render_sys();
+                let render_sys = p_render_sys.read();
+                for (sampler, p_texture) in &material.textures {
+                    let image_name = p_texture.lock().get_image().and_then(|image| {render_sys.find_image_name(&image)});
+                    if let Some(image_name) = image_name {
+                        data.push(Data::Texture(sampler.clone(), image_name));
+                    }
+                }
+                drop(render_sys);
                 RenderPipelineSystem::register_shader(self.layer, material.shader.clone());
                 RenderPipelineSystem::render_shader(self.layer, material.shader.clone(), data);
             }
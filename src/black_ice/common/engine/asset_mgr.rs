@@ -1,28 +1,31 @@
 // this will be used to load asset packs into our game!!
 
 
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashMap;
 use std::error::Error;
-use std::hash::Hash;
 #[cfg(target_os="linux")]use std::os::unix::fs::MetadataExt;
 #[cfg(target_os="windows")]use std::os::windows::fs::MetadataExt;
-use std::str::FromStr;
 use std::{fmt, io};
 use std::{fs::File, path::PathBuf};
 use std::sync::Arc;
-
-use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
-use colored::*;
-use futures::future::err;
+use std::ops::Deref;
+use std::cell::RefCell;
+use std::time::{Duration, Instant, SystemTime};
+
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use memmap2::Mmap;
+use futures::executor::ThreadPool;
+use once_cell::sync::Lazy;
 use parking_lot::*;
 use crate::black_ice::common::Env;
 use crate::black_ice::common::engine::asset_types::*;
+use crate::black_ice::common::engine::asset_types::texture::{Texture, DDS_HEADER_SIZE};
+use crate::black_ice::common::components::component_system::{Value, ValueBuilder};
 
-use super::input;
 use super::pipeline::RenderPipelineSystem;
 
 #[derive(PartialEq, Clone)]
-enum PathType {
+pub enum PathType {
 
     DIRECTORY,
     FILE,
@@ -30,14 +33,16 @@ enum PathType {
 }
 //region Path Rep
 #[derive(Clone)]
-struct PathRep {
+pub struct PathRep {
     pub name: String,
     pub path_type: PathType,
     pub meta_data: HashMap<String, String>,
     file_path: Option<PathBuf>,
     next: Option<HashMap<String, PathRep>>,
     data_offset: Option<usize>,
-    data_size: Option<u64>
+    data_size: Option<u64>,
+    checksum: Option<u32>,// only stored in version 1 asset packs and above
+    compression: AssetCompression,
 }
 
 impl PathRep {
@@ -49,7 +54,9 @@ impl PathRep {
             file_path:None,
             next: None,
             data_offset: None,
-            data_size: None
+            data_size: None,
+            checksum: None,
+            compression: AssetCompression::NONE,
         }
     }
 
@@ -103,14 +110,117 @@ impl PathRep {
     pub fn get_file_path(&self) -> Option<PathBuf> {
         self.file_path.clone()
     }
+
+    pub fn get_meta_data(&self) -> &HashMap<String, String> {
+        &self.meta_data
+    }
+
+    pub fn set_checksum(&mut self, checksum: u32){
+        if self.path_type == PathType::FILE{
+            self.checksum = Some(checksum);
+        }
+    }
+
+    pub fn get_checksum(&self) -> Option<u32> {
+        self.checksum.clone()
+    }
+
+    // finds a path below this one, e.g. ["textures", "brick.png"]
+    pub fn find(&self, parts: &[&str]) -> Option<&PathRep> {
+        let mut temp = self;
+        for part in parts {
+            temp = temp.next.as_ref()?.get(*part)?;
+        }
+        Some(temp)
+    }
+
+    // adds a file into the directory tree, creating any directories along the way
+    fn insert_path(&mut self, parts: &[&str], rep: PathRep) {
+        if self.next.is_none() {
+            self.set_next(HashMap::new());
+        }
+        let next = self.next.as_mut().expect("Cannot insert a path into a file!! This is a bug!!");
+        if parts.len() == 1 {
+            next.insert(parts[0].to_string(), rep);
+            return;
+        }
+        next.entry(parts[0].to_string())
+            .or_insert_with(|| {PathRep::new(parts[0].to_string(), PathType::DIRECTORY, None)})
+            .insert_path(&parts[1..], rep);
+    }
 }
 //endregion
 
+// How the data of an entry is stored within an asset pack
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum AssetCompression {
+    NONE,
+    ZLIB,
+}
+
+impl AssetCompression {
+    // the key used for the compression in an entry's metadata map
+    pub const META_KEY: &'static str = "compression";
+
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::NONE),
+            1 => Some(Self::ZLIB),
+            _ => None
+        }
+    }
+
+    fn as_u8(&self) -> u8 {
+        match self {
+            Self::NONE => 0,
+            Self::ZLIB => 1,
+        }
+    }
+
+    pub fn from_meta(meta_data: &HashMap<String, String>) -> Option<Self> {
+        match meta_data.get(Self::META_KEY).map(|s| {s.as_str()}) {
+            None | Some("none") => Some(Self::NONE),
+            Some("zlib") | Some("deflate") => Some(Self::ZLIB),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NONE => "none",
+            Self::ZLIB => "zlib",
+        }
+    }
+
+    fn compress(&self, data: Vec<u8>) -> Result<Vec<u8>, io::Error> {
+        match self {
+            Self::NONE => Ok(data),
+            Self::ZLIB => {
+                let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(&data)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, io::Error> {
+        match self {
+            Self::NONE => Ok(data.to_vec()),
+            Self::ZLIB => {
+                let mut out: Vec<u8> = vec![];
+                flate2::read::ZlibDecoder::new(data).read_to_end(&mut out)?;
+                Ok(out)
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct AssetPack{
     pub asset_location: String,// the physical location of the asset pack
     version: u32,
-    rep: PathRep
+    rep: PathRep,
+    map: Arc<Mmap>,// the whole pack is mapped so that uncompressed entries can be handed out without copying them
 }
 
 #[derive(Debug)]
@@ -118,6 +228,11 @@ pub enum AssetPackLoadErrorStackTrace {
     IoError(io::Error),
     FmtError(fmt::Error),
     DelimError,
+    HeaderError,
+    VersionError(u32),
+    ChecksumError(String),
+    CompressionError(u8),
+    CompressionNameError(String),// version 0 packs name the compression in the metadata instead
 
 }
 
@@ -126,7 +241,12 @@ impl fmt::Display for AssetPackLoadErrorStackTrace{
         match self {
             Self::IoError(e) => write!(f, "{}", e),
             Self::FmtError(e) => write!(f, "{}", e),
-            Self::DelimError => write!(f, "Deliminator is missing and/or corrupted. Please re-compile the asset pack again or call an issue on the git repo!")
+            Self::DelimError => write!(f, "Deliminator is missing and/or corrupted. Please re-compile the asset pack again or call an issue on the git repo!"),
+            Self::HeaderError => write!(f, "The asset pack header is missing and/or corrupted. Please re-compile the asset pack again!"),
+            Self::VersionError(v) => write!(f, "Asset pack version {} is not supported by this version of the engine!", v),
+            Self::ChecksumError(p) => write!(f, "Checksum of {} does not match. The asset pack is corrupted, please re-compile the asset pack again!", p),
+            Self::CompressionError(c) => write!(f, "Unknown compression type {}. The asset pack may have been made with a newer version of the engine!", c),
+            Self::CompressionNameError(c) => write!(f, "Unknown compression {} in the asset metadata. The asset pack may have been made with a newer version of the engine!", c),
         }
     }
 }
@@ -146,6 +266,40 @@ impl fmt::Display for AssetPackLoadError {
 
 impl Error for AssetPackLoadError {}
 
+#[derive(Debug)]
+pub enum AssetLoadError {
+    UnknownPack(String),// the name of the pack or folder
+    UnknownPath(String),// the full asset path
+    Io(String, io::Error),// the file that we failed to read
+    MetadataMismatch(String, String),// the asset path and what was wrong with its metadata
+    CorruptPack(String, AssetPackLoadErrorStackTrace),// the location of the asset pack
+    InvalidData(String, io::Error),// the asset path, and why its data couldn't be loaded
+}
+
+impl fmt::Display for AssetLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownPack(p) => write!(f, "No asset pack or folder called {} has been loaded. Are you sure that the asset folder has been exposed?", p),
+            Self::UnknownPath(p) => write!(f, "Failed to find the asset {}!!", p),
+            Self::Io(p, e) => write!(f, "Failed to read {}: {}", p, e),
+            Self::MetadataMismatch(p, m) => write!(f, "The metadata of {} does not match: {}", p, m),
+            Self::CorruptPack(p, e) => write!(f, "The asset pack {} is corrupted: {}", p, e),
+            Self::InvalidData(p, e) => write!(f, "The data of {} can't be loaded: {}", p, e),
+        }
+    }
+}
+
+impl Error for AssetLoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(_, e) => Some(e),
+            Self::CorruptPack(_, e) => Some(e),
+            Self::InvalidData(_, e) => Some(e),
+            _ => None
+        }
+    }
+}
+
 impl AssetPack{
     /** Format: 
     * version
@@ -174,204 +328,296 @@ impl AssetPack{
     */
     fn version_0(buff_reader: &mut BufReader<File>, paths: &mut HashMap<String, PathRep>, asset_file_metadata: &mut HashMap<String, Vec<String>>, asset_pack_name: String) -> Result<(), AssetPackLoadError>{
         
-        fn read_until_or(r: &mut BufReader<File>, delim: u8, _count: usize, buf: &mut [u8]) -> Result<usize, std::io::Error>{
-            let mut read = 0;
-            let _early = false;
-            let mut vec:Vec<u8> = vec![];
-            'main: loop {
-                let (done, used) = {
-                    let available = match r.fill_buf() {
-                        Ok(n) => n,
-                        Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
-                        Err(e) => return Err(e),
-                    };
-                    // check if the available space 
-                    
-                    match available.iter().position(|c| c == &delim){// in future replace with memchr::memchr crate!!
-                        Some(i) => {
-                            if i > buf.len() - read {
-                                // only copy acceptable ammount
-                                let selected = available.get(..buf.len() - read).expect("Failed to read buffer. Couldn't read buffer of large size into remainder of buffer.");
-                                vec.extend_from_slice(selected);
-                                (true, buf.len() - read)
-                            }
-                            else{
-                                vec.extend_from_slice(available.get(..i).expect("Failed to get sub section!!"));
-                                (true, i)
-                            }
-                        },
-                        None => {
-                            if available.len() > buf.len() - read {
-                                // only copy acceptable ammount
-                                let selected = available.get(..buf.len() - read).expect("Failed to read buffer. Couldn't read buffer of large size into remainder of buffer.");
-                                vec.extend_from_slice(selected);
-                                (true, buf.len() - read)
-                            }
-                            else{
-                                vec.extend_from_slice(available);
-                                (false, available.len())
-                            }
-                        }
-                    }
-                    
-                };
-                r.consume(used);
-                read += used;
-                if done || used == 0 {
-                    break 'main;
-                }
+        fn read_byte(r: &mut BufReader<File>) -> Result<u8, AssetPackLoadError> {
+            let mut byte: [u8; 1] = [0;1];
+            match r.read_exact(&mut byte) {
+                Ok(_) => Ok(byte[0]),
+                Err(e) => Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::IoError(e)})
             }
-            vec.as_slice().read_exact(buf).expect("Could not read into buffer!!");
-            return Ok(read);
         }
 
-        let mut is_finished = false;
-        let mut _separator = 0;
-        // check if the next character is a <
-        // if not, then we crash out!!
-        let mut delim: [u8; 1] = [0;1];
-        match buff_reader.read_exact(&mut delim){
-            Ok(_) => {},
-            Err(e) => {return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::IoError(e)})}
+        fn expect_delim(r: &mut BufReader<File>, delim: u8) -> Result<(), AssetPackLoadError> {
+            if read_byte(r)? != delim {
+                return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::DelimError});
+            }
+            Ok(())
+        }
+
+        // the path name is ascii and null terminated, with a max length of 512 characters
+        // we have already read the first character so that we could check if it was a closing deliminator
+        fn read_path_name(r: &mut BufReader<File>, first: u8) -> Result<String, AssetPackLoadError> {
+            let mut path_name_u8: Vec<u8> = vec![first];
+            loop {
+                let c = read_byte(r)?;
+                if c == b'\0' {
+                    break;
+                }
+                if path_name_u8.len() >= 512 {
+                    return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::DelimError});
+                }
+                path_name_u8.push(c);
+            }
+            Ok(String::from_utf8_lossy(&path_name_u8).to_string())
         }
-        if delim.is_ascii(){
-            let temp: String = String::from_utf8(delim.to_vec()).expect("Failed to parse deliminator. The file may be corrupt!!");
-            if temp == "<"{
-                // this is correct, there is no corruption!!
-                _separator += 1;
+
+        // This will commit the directory at the top of the stack into its parent
+        // returns true when we have closed the root of the asset pack
+        fn close_dir(stack: &mut Vec<(String, HashMap<String, PathRep>)>, paths: &mut HashMap<String, PathRep>) -> bool {
+            let (name, children) = stack.pop().expect("Closed more directories than were opened!! This is a bug!!");
+            if let Some(parent) = stack.last_mut() {
+                let mut r = PathRep::new(name.clone(), PathType::DIRECTORY, None);
+                r.set_next(children);
+                parent.1.insert(name, r);
+                false
             }
             else {
-                return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::DelimError});
+                // we have fully read the file!!
+                *paths = children;
+                true
             }
         }
-        else{
-            return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::DelimError});
-        }
-        delim.copy_from_slice(b" ");// clear the delim so that we do not have
-        let mut paths_unfinished_vec: Vec<(String, u8)> = vec![(asset_pack_name, 1)];// This will store our paths that we have so far read but haven't totally commited!!
-        // To commit a PathRep, we must encounter a closing deliminator
-        let mut path_reps_vec: Vec<HashMap<String, PathRep>> = vec![HashMap::new()];// This stores the set of paths within a current directory!!
-        let mut current_dir: usize = 0;// We start at directory 0, the root directory
-        // This will be empty when we start since there are no directories stored yet!!
 
-        while !is_finished {
-            // get the path_name - the max character length is 512, and the deliminator is \0
-            // make sure to go past the separator 
-            // for the first itteration we will always treat the data as a dirtectory, We will not accept file only asset packs in this version
-            
-            // Get the path_name
-            let mut path_name_u8: [u8; 512] = [0;512];
-            let length = read_until_or(buff_reader, b'\0', 512, &mut path_name_u8).expect("Failed to read path name!!");
-            let path_name = unsafe {String::from_raw_parts(path_name_u8.as_mut_ptr(), length, 512)};
-            // now we read the next byte as a boolean value
-            // We want to use a byte here to ensure that we have consistant spacing for the entire file!!
-            let mut path_type_u8: [u8; 1] = [0;1];
-            buff_reader.read_exact(&mut path_type_u8).expect("Failed to read path type!!");
-            // now we just simply check if this byte is 0 or not 0
-            match path_type_u8[0].clone() {
-                0 => {// This is the file case
-                    // We will read until the deliminator
-                    // Since we don't know the size of our data and don't want to store this data in memory forever, we will have to quickly 
-                    let mut size_u8: [u8; 8] = [0;8];
-                    buff_reader.read_exact(&mut size_u8).expect("Failed to read file size!!");
-                    let size: u64 = u64::from_le_bytes(size_u8);
-                    // now we need to get the metadata of the file, This must be done using string, soo we will keep reading a new character
-                    // into some string
-                    let mut metadata: String = String::new();
-                    // read first character to check if there is metadata
-                    let mut mt: [u8; 1] = [0;1];
-                    buff_reader.read_exact(&mut mt).expect("Cannot read metadata!!");
-                    if mt[0] == b'[' {
-                        // we have metadata
-                        // now we keep reading till we reach the end of the ascii list!!
-                        loop {
-                            buff_reader.read_exact(&mut mt).expect("Cannot read metadata!!");
-                            if mt[0] == b']' {
-                                break;
+        // check if the next character is a <
+        // if not, then we crash out!!
+        expect_delim(buff_reader, b'<')?;
+
+        // This will store our directories that we have so far read but haven't totally commited!!
+        // To commit a directory, we must encounter a closing deliminator
+        // We start at directory 0, the root directory
+        let mut stack: Vec<(String, HashMap<String, PathRep>)> = vec![(asset_pack_name, HashMap::new())];
+
+        'entries: loop {
+            // we are either at the start of an entry or at the closing deliminator of an empty directory
+            let first = read_byte(buff_reader)?;
+            if first == b'>' {
+                if close_dir(&mut stack, paths) {
+                    return Ok(());
+                }
+            }
+            else {
+                // Get the path_name
+                let path_name = read_path_name(buff_reader, first)?;
+                // now we read the next byte as a boolean value
+                // We want to use a byte here to ensure that we have consistant spacing for the entire file!!
+                let path_type_u8 = read_byte(buff_reader)?;
+                match path_type_u8 {
+                    0 => {// This is the file case
+                        let mut size_u8: [u8; 8] = [0;8];
+                        if let Err(e) = buff_reader.read_exact(&mut size_u8) {
+                            return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::IoError(e)});
+                        }
+                        let size: u64 = u64::from_le_bytes(size_u8);
+                        // now we need to get the metadata of the file, This must be done using string, soo we will keep reading a new character
+                        // into some string
+                        let mut metadata: String = String::new();
+                        // read first character to check if there is metadata
+                        let mut mt = read_byte(buff_reader)?;
+                        if mt == b'[' {
+                            // we have metadata
+                            // now we keep reading till we reach the end of the ascii list!!
+                            loop {
+                                mt = read_byte(buff_reader)?;
+                                if mt == b']' {
+                                    break;
+                                }
+                                metadata.push(mt.into());
                             }
-                            metadata.push(mt[0].into());
+                            mt = read_byte(buff_reader)?;
                         }
-                        
-                    }
-                    else{
-                        // we don't have metadata
-                        buff_reader.seek(io::SeekFrom::Current(-1)).expect("Failed to reset head!!");
-                    }
-                    let start = buff_reader.seek(io::SeekFrom::Current(0)).expect("Failed to get current position of head");
-                    
-                    // add the asset metadata to asset_file_metadata
-                    let metadata_list: Vec<String> = metadata.clone().split(',').map(|s| {String::from(s)}).collect();
-                    asset_file_metadata.insert(path_name.clone(), metadata_list.clone());
+                        // we don't have metadata, or we have finished reading it. Either way we must now be at the start of the data
+                        if mt != b'<' {
+                            return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::DelimError});
+                        }
+                        let start = match buff_reader.stream_position() {
+                            Ok(s) => s,
+                            Err(e) => return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::IoError(e)})
+                        };
+
+                        // add the asset metadata to asset_file_metadata
+                        let metadata_list: Vec<String> = metadata.split(',').filter(|s| {!s.is_empty()}).map(|s| {String::from(s)}).collect();
+                        asset_file_metadata.insert(path_name.clone(), metadata_list.clone());
 
-                    let mut metadata_map: HashMap<String,String> = HashMap::new();
+                        let mut metadata_map: HashMap<String,String> = HashMap::new();
 
-                    for mdat in metadata_list {
-                        let mdat_split = mdat.split(":").map(|f| {String::from(f)}).collect::<Vec<String>>();
-                        metadata_map.insert(mdat_split[0].clone(), mdat_split[1].clone());
+                        for mdat in metadata_list {
+                            if let Some((key, value)) = mdat.split_once(':') {
+                                metadata_map.insert(key.to_string(), value.to_string());
+                            }
+                        }
+
+                        // version 0 has no compression flag, so the metadata is the only place it can be recorded
+                        let compression = match AssetCompression::from_meta(&metadata_map) {
+                            Some(c) => c,
+                            None => {
+                                let name = metadata_map.get(AssetCompression::META_KEY).cloned().unwrap_or_default();
+                                return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::CompressionNameError(name)});
+                            }
+                        };
+                        let mut path_rep = PathRep::new(path_name.clone(), PathType::FILE, Some(metadata_map));
+                        path_rep.set_data_offset(start.try_into().expect("Failed to convert u64 offset to usize offset!!"));
+                        path_rep.set_data_size(size);
+                        path_rep.compression = compression;
+                        stack.last_mut().unwrap().1.insert(path_name, path_rep);
+                        // Since we don't want to store this data in memory forever, we will skip over it and read it when the asset is loaded
+                        if let Err(e) = buff_reader.seek_relative(size.try_into().expect("File is too large to seek over!!")) {
+                            return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::IoError(e)});
+                        }
+                        expect_delim(buff_reader, b'>')?;
+                    }
+                    _ => {// This is the Directory case!!
+                        // We will commit our current path name to our unfinished stack
+                        // This will ensure that the next run will focus on a new set of data
+                        expect_delim(buff_reader, b'<')?;
+                        stack.push((path_name, HashMap::new()));
+                        continue 'entries;
                     }
-                    
-                    let mut path_rep = PathRep::new(path_name.clone(), PathType::FILE, Some(metadata_map));
-                    path_rep.set_data_offset(start.try_into().expect("Failed to convert u64 offset to usize offset!!"));
-                    path_rep.set_data_size(size);
-                    path_reps_vec[current_dir].insert(path_name, path_rep);
-                    let seek: u64 = (size + 2).try_into().unwrap();
-                    let _ = buff_reader.seek(io::SeekFrom::Current(seek.try_into().unwrap()));
-                }
-                _ => {// This is the Directory case!!
-                    // We will increment our separator value
-                    // This will ensure that the next run will focus on a new set of data
-                    // We will also commit our current path name and path type to our unfinished vector
-                    let _ = buff_reader.seek(io::SeekFrom::Current(1));
-                    paths_unfinished_vec.push((path_name, path_type_u8[0]));
-                    path_reps_vec.push(HashMap::new());
-                    current_dir+= 1;
                 }
             }
-            // now check the next bit if it closes the file
-            let mut byte: [u8; 1] = [0;1];
-            let _ = buff_reader.read_exact(&mut byte);
-            match &byte{
-                b">" =>{
-                    if current_dir > 0 {
-                        // we close the directory and add it to the previous pathrep
-                        let (a,_b) = &paths_unfinished_vec[current_dir - 1];
-                        // we will add this directory as a pathrep
-                        let mut r = PathRep::new(a.clone(), PathType::DIRECTORY, None);
-                        r.set_next(path_reps_vec[current_dir -1].clone());
-
-                        current_dir -= 1;
-                        path_reps_vec[current_dir].insert(a.to_string(), r);
-                    }
-                    else{
-                        // we have fully read the file!!
-                        // we will now try to exit from here
-                        *paths = path_reps_vec[0].clone();
-                        path_reps_vec.clear();
-                        paths_unfinished_vec.clear();
-                        is_finished = true;
-                    }
-                    
 
-                }
-                _ =>{
-                    return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::DelimError});
+            // now check the next byte. It either tells us there are more entries or it closes the directory
+            loop {
+                match read_byte(buff_reader)? {
+                    b'|' => continue 'entries,
+                    b'>' => {
+                        if close_dir(&mut stack, paths) {
+                            return Ok(());
+                        }
+                    },
+                    _ => {
+                        return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::DelimError});
+                    }
                 }
             }
+        }
+    }
 
+    const MAGIC: &'static [u8; 4] = b"BIPK";
+
+    /** Format:
+    * version (1 or 2)
+    * header
+    *     magic ("BIPK")
+    *     entry_count (32 bit integer)
+    *     index_offset (64 bit integer, from the start of the file)
+    *     index_size (64 bit integer, in bytes)
+    *     index_checksum (CRC32 of the index table. In version 2 it is of the header before it too)
+    * data (the data of every file, one after another)
+    * index table (one entry for every file, directories are only stored as part of the path)
+    *     path_length (16 bit integer)
+    *     path (utf8, relative to the root of the pack and seperated by /)
+    *     data_offset (64 bit integer, from the start of the file)
+    *     data_size (64 bit integer, the size of the stored data)
+    *     checksum (CRC32 of the stored data)
+    *     compression (a byte, 0 for none and 1 for zlib, also stored in the metadata under "compression")
+    *     metadata_count (32 bit integer)
+    *     metadata (metadata_count pairs of a 16 bit key length, key, 32 bit value length, value)
+    *
+    * All integers are little endian. Since the index table is flat, we can read it in one go without having to
+    * go through all of the data, and we can check it for corruption before we trust any of the offsets.
+    */
+    fn version_1(buff_reader: &mut BufReader<File>, paths: &mut HashMap<String, PathRep>, asset_file_metadata: &mut HashMap<String, Vec<String>>, checksum_header: bool) -> Result<(), AssetPackLoadError>{
+
+        fn io_err(e: io::Error) -> AssetPackLoadError {
+            AssetPackLoadError { source: AssetPackLoadErrorStackTrace::IoError(e)}
         }
-        
-        return Ok(());
+
+        fn take<'a>(index: &'a [u8], p: &mut usize, n: usize) -> Result<&'a [u8], AssetPackLoadError> {
+            let out = p.checked_add(n).and_then(|end| {index.get(*p..end)}).ok_or(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::HeaderError})?;
+            *p += n;
+            Ok(out)
+        }
+
+        let mut header: [u8; 28] = [0; 28];
+        buff_reader.read_exact(&mut header).map_err(io_err)?;
+        if &header[0..4] != AssetPack::MAGIC {
+            return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::HeaderError});
+        }
+        let entry_count = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let index_offset = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let index_size = u64::from_le_bytes(header[16..24].try_into().unwrap());
+        let index_checksum = u32::from_le_bytes(header[24..28].try_into().unwrap());
+
+        // nothing in the header can be trusted until the checksum has been checked, so the index table has to fit in
+        // the file before we make space for it
+        let file_len = buff_reader.get_ref().metadata().map_err(io_err)?.len();
+        let index_end = index_offset.checked_add(index_size).ok_or(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::HeaderError})?;
+        if index_end > file_len {
+            return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::HeaderError});
+        }
+        let index_size: usize = index_size.try_into().map_err(|_| {AssetPackLoadError { source: AssetPackLoadErrorStackTrace::HeaderError}})?;
+
+        buff_reader.seek(SeekFrom::Start(index_offset)).map_err(io_err)?;
+        let mut index: Vec<u8> = vec![0; index_size];
+        buff_reader.read_exact(&mut index).map_err(io_err)?;
+
+        let mut crc = flate2::Crc::new();
+        if checksum_header {
+            crc.update(&header[0..24]);
+        }
+        crc.update(&index);
+        if crc.sum() != index_checksum {
+            return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::ChecksumError("the index table".to_string())});
+        }
+
+        let mut root = PathRep::new(String::new(), PathType::DIRECTORY, None);
+        root.set_next(HashMap::new());
+        let mut p: usize = 0;
+        for _ in 0..entry_count {
+            let path_length = u16::from_le_bytes(take(&index, &mut p, 2)?.try_into().unwrap()) as usize;
+            let path = String::from_utf8_lossy(take(&index, &mut p, path_length)?).to_string();
+            let data_offset = u64::from_le_bytes(take(&index, &mut p, 8)?.try_into().unwrap());
+            let data_size = u64::from_le_bytes(take(&index, &mut p, 8)?.try_into().unwrap());
+            let checksum = u32::from_le_bytes(take(&index, &mut p, 4)?.try_into().unwrap());
+            let compression_u8 = take(&index, &mut p, 1)?[0];
+            let compression = AssetCompression::from_u8(compression_u8)
+                .ok_or(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::CompressionError(compression_u8)})?;
+            let metadata_count = u32::from_le_bytes(take(&index, &mut p, 4)?.try_into().unwrap());
+            let mut metadata_map: HashMap<String, String> = HashMap::new();
+            for _ in 0..metadata_count {
+                let key_length = u16::from_le_bytes(take(&index, &mut p, 2)?.try_into().unwrap()) as usize;
+                let key = String::from_utf8_lossy(take(&index, &mut p, key_length)?).to_string();
+                let value_length = u32::from_le_bytes(take(&index, &mut p, 4)?.try_into().unwrap()) as usize;
+                let value = String::from_utf8_lossy(take(&index, &mut p, value_length)?).to_string();
+                metadata_map.insert(key, value);
+            }
+
+            let parts = path.split('/').filter(|s| {!s.is_empty()}).collect::<Vec<&str>>();
+            if parts.is_empty() {
+                return Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::HeaderError});
+            }
+            let name = parts[parts.len() - 1].to_string();
+            asset_file_metadata.insert(name.clone(), metadata_map.iter().map(|(k, v)| {format!("{}:{}", k, v)}).collect());
+
+            let mut path_rep = PathRep::new(name, PathType::FILE, Some(metadata_map));
+            path_rep.set_data_offset(data_offset.try_into().map_err(|_| {AssetPackLoadError { source: AssetPackLoadErrorStackTrace::HeaderError}})?);
+            path_rep.set_data_size(data_size);
+            path_rep.set_checksum(checksum);
+            path_rep.compression = compression;
+            root.insert_path(&parts, path_rep);
+        }
+
+        *paths = root.next.take().unwrap();
+        Ok(())
     }
 
     pub fn load(path: PathBuf) -> Self {
-        assert!(path.is_file());
-        assert_eq!("pkg", path.extension().unwrap());
+        match AssetPack::try_load(path) {
+            Ok(pack) => pack,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    pub fn try_load(path: PathBuf) -> Result<Self, AssetLoadError> {
+        let location = path.to_str().unwrap().to_string();
+        if !path.is_file() || path.extension().map(|e| {e != "pkg"}).unwrap_or(true) {
+            return Err(AssetLoadError::UnknownPack(location));
+        }
         // this is a file and we can load it correctly!!
-        let asset_pack_file = File::open(path.clone()).expect("Failed to open file!!");
+        let asset_pack_file = File::open(path.clone()).map_err(|e| {AssetLoadError::Io(location.clone(), e)})?;
         let temp = path.as_path().file_name().unwrap();
         let temp2 = PathBuf::from(temp.to_str().unwrap());
-        let _temp3 = temp2.file_stem().unwrap();
-        let filename = temp2.to_str().unwrap();
+        // the pack is addressed by its name without the extension, the same way asset folders are (ASSET:pack/...)
+        let pack_name = temp2.file_stem().unwrap().to_str().unwrap();
         let mut buff_reader = BufReader::new(asset_pack_file);
         let mut paths = HashMap::<String, PathRep>::new();
         let mut metadata = HashMap::<String, Vec<String>>::new();
@@ -382,31 +628,195 @@ impl AssetPack{
         // backwards compatability in fucture if in case updates are made to the file scheme
         let mut temp: [u8; 4] = [0;4];
         buff_reader.read_exact(&mut temp)
-            .expect(format!("{}", "Failed to read file. There seems to be no data in this file or the file may be corrupted!!!".red()).as_str());
+            .map_err(|e| {AssetLoadError::CorruptPack(location.clone(), AssetPackLoadErrorStackTrace::IoError(e))})?;
         
         version = u32::from_le_bytes(temp);// we will assume little endian bytes for all files loaded!!
 
 
         let error = match version.clone(){
-            _ => {
-                AssetPack::version_0(&mut buff_reader, &mut paths, &mut metadata, filename.to_string())
+            0 => {
+                AssetPack::version_0(&mut buff_reader, &mut paths, &mut metadata, pack_name.to_string())
+            },
+            1 => {
+                AssetPack::version_1(&mut buff_reader, &mut paths, &mut metadata, false)
+            },
+            2 => {
+                AssetPack::version_1(&mut buff_reader, &mut paths, &mut metadata, true)
+            },
+            v => {
+                Err(AssetPackLoadError { source: AssetPackLoadErrorStackTrace::VersionError(v)})
             }
         };
 
-        match error {
-            Err(e) => {
-                //println!("{}", e);
-                panic!("")
-            },
-            Ok(_) => {}
+        if let Err(e) = error {
+            return Err(AssetLoadError::CorruptPack(location, e.source));
         }
 
-        let mut rep = PathRep::new(filename.to_string(), PathType::DIRECTORY, None);// this will always be a directory
+        let mut rep = PathRep::new(pack_name.to_string(), PathType::DIRECTORY, None);// this will always be a directory
         rep.set_next(paths);
-        Self{
-            asset_location: filename.to_string(),
+        // the pack must not be edited while the engine is running, otherwise the mapped data will change under us!!
+        let map = unsafe { Mmap::map(buff_reader.get_ref()) }.map_err(|e| {AssetLoadError::Io(location.clone(), e)})?;
+        Ok(Self{
+            asset_location: location,
             version: version,
-            rep: rep
+            rep: rep,
+            map: Arc::new(map)
+        })
+    }
+
+    /// Builds an asset pack from an `AssetFolder`, writing it out in the latest format (see `version_1`).
+    /// The root directory of the folder is not written as an entry, so a pack built from `assets/` and saved as
+    /// `assets.pkg` will be addressed the same way as the folder was (`ASSET:assets/...`).
+    pub fn build(folder: &AssetFolder, out: PathBuf) -> Result<(), io::Error> {
+        AssetPack::write_version_1(folder, out, None, &AssetPack::read_entry)
+    }
+
+    /// Builds an asset pack where the data and metadata of each file is given by `read` instead of being copied
+    /// from the folder as is. This is how the asset cooker writes out its cooked files.
+    pub fn build_with(folder: &AssetFolder, out: PathBuf, manifest: Option<&AssetManifest>, read: &dyn Fn(&String, &PathRep) -> Result<(Vec<u8>, HashMap<String, String>), io::Error>) -> Result<(), io::Error> {
+        AssetPack::write_version_1(folder, out, manifest, read)
+    }
+
+    // reads the file of an entry straight from the folder
+    pub fn read_entry(_rep_path: &String, rep: &PathRep) -> Result<(Vec<u8>, HashMap<String, String>), io::Error> {
+        let file_path = rep.get_file_path().expect("No file path associated with this path!! This is a bug!!");
+        let mut data: Vec<u8> = vec![];
+        File::open(file_path)?.read_to_end(&mut data)?;
+        Ok((data, rep.meta_data.clone()))
+    }
+
+    /// Builds an asset pack out of only the files of the folder that are in the manifest, e.g. everything that a level needs.
+    /// Use `AssetManager::expand_manifest` first if the manifest only lists the roots!!
+    pub fn build_from_manifest(folder: &AssetFolder, manifest: &AssetManifest, out: PathBuf) -> Result<(), io::Error> {
+        AssetPack::write_version_1(folder, out, Some(manifest), &AssetPack::read_entry)
+    }
+
+    fn write_version_1(folder: &AssetFolder, out: PathBuf, manifest: Option<&AssetManifest>, read: &dyn Fn(&String, &PathRep) -> Result<(Vec<u8>, HashMap<String, String>), io::Error>) -> Result<(), io::Error> {
+        // gather all of the files along with their path relative to the root of the pack
+        let mut files = folder.entries();
+        if let Some(manifest) = manifest {
+            let prefix = "ASSET:".to_string() + folder.rep.name.as_str() + "/";
+            files.retain(|(rep_path, _)| {manifest.assets.contains(&(prefix.clone() + rep_path.as_str()))});
+        }
+        // sort the entries so that the same folder always produces the same pack
+        files.sort_by(|a, b| {a.0.cmp(&b.0)});
+
+        let mut writer = BufWriter::new(File::create(out)?);
+        // version 2 is the version 1 layout with the header checksummed too
+        writer.write_all(&2_u32.to_le_bytes())?;
+        // we don't know where the index table goes yet, so we leave space for the header and come back to it
+        writer.write_all(&[0; 28])?;
+        let mut offset: u64 = 4 + 28;
+
+        let mut index: Vec<u8> = vec![];
+        for (rep_path, rep) in &files {
+            if rep_path.len() > u16::MAX as usize {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Path {} is too long to be stored in an asset pack!!", rep_path)));
+            }
+            let (mut data, mut meta_data) = read(rep_path, rep)?;
+
+            // the metadata can ask for a specific compression, otherwise we only compress if it actually saves space
+            let compression = match AssetCompression::from_meta(&meta_data) {
+                Some(c) if meta_data.contains_key(AssetCompression::META_KEY) => c,
+                Some(_) => {
+                    let compressed = AssetCompression::ZLIB.compress(data.clone())?;
+                    if compressed.len() < data.len() {
+                        data = compressed;
+                        AssetCompression::ZLIB
+                    }
+                    else {
+                        AssetCompression::NONE
+                    }
+                },
+                None => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown compression {} for {}!!", meta_data[AssetCompression::META_KEY], rep_path)));
+                }
+            };
+            if meta_data.contains_key(AssetCompression::META_KEY) {
+                data = compression.compress(data)?;
+            }
+            meta_data.insert(AssetCompression::META_KEY.to_string(), compression.as_str().to_string());
+
+            let mut crc = flate2::Crc::new();
+            crc.update(&data);
+            writer.write_all(&data)?;
+
+            index.extend_from_slice(&(rep_path.len() as u16).to_le_bytes());
+            index.extend_from_slice(rep_path.as_bytes());
+            index.extend_from_slice(&offset.to_le_bytes());
+            index.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            index.extend_from_slice(&crc.sum().to_le_bytes());
+            index.push(compression.as_u8());
+            let mut keys = meta_data.keys().collect::<Vec<&String>>();
+            keys.sort();
+            index.extend_from_slice(&(keys.len() as u32).to_le_bytes());
+            for key in keys {
+                let value = &meta_data[key];
+                index.extend_from_slice(&(key.len() as u16).to_le_bytes());
+                index.extend_from_slice(key.as_bytes());
+                index.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                index.extend_from_slice(value.as_bytes());
+            }
+            offset += data.len() as u64;
+        }
+        writer.write_all(&index)?;
+
+        let mut header: Vec<u8> = AssetPack::MAGIC.to_vec();
+        header.extend_from_slice(&(files.len() as u32).to_le_bytes());
+        header.extend_from_slice(&offset.to_le_bytes());
+        header.extend_from_slice(&(index.len() as u64).to_le_bytes());
+        let mut crc = flate2::Crc::new();
+        crc.update(&header);
+        crc.update(&index);
+        header.extend_from_slice(&crc.sum().to_le_bytes());
+        writer.seek(SeekFrom::Start(4))?;
+        writer.write_all(&header)?;
+        writer.flush()
+    }
+
+    pub fn get_name(&self) -> String {
+        self.rep.name.clone()
+    }
+
+    // gets the data of a single file stored in the asset pack
+    // uncompressed entries are just a view into the mapped pack, so they don't get copied into memory
+    fn read_data(&self, rep: &PathRep) -> Result<AssetBytes, AssetLoadError> {
+        AssetPack::read_mapped(&self.map, &self.asset_location, rep)
+    }
+
+    // this doesn't need the pack itself, so the asset manager doesn't need to be locked while we read
+    fn read_mapped(map: &Arc<Mmap>, asset_location: &String, rep: &PathRep) -> Result<AssetBytes, AssetLoadError> {
+        let corrupt = |e: AssetPackLoadErrorStackTrace| {AssetLoadError::CorruptPack(asset_location.clone(), e)};
+        // the entry comes from the pack, so it can't be trusted to be in range
+        let offset = rep.get_data_offset().ok_or(corrupt(AssetPackLoadErrorStackTrace::HeaderError))?;
+        let size: usize = rep.get_data_size().and_then(|s| {s.try_into().ok()}).ok_or(corrupt(AssetPackLoadErrorStackTrace::HeaderError))?;
+        let data = offset.checked_add(size).and_then(|end| {map.get(offset..end)})
+            .ok_or(corrupt(AssetPackLoadErrorStackTrace::IoError(io::Error::new(io::ErrorKind::UnexpectedEof, format!("{} goes past the end of the asset pack!!", rep.name)))))?;
+        if let Some(checksum) = rep.get_checksum() {
+            let mut crc = flate2::Crc::new();
+            crc.update(data);
+            if crc.sum() != checksum {
+                return Err(corrupt(AssetPackLoadErrorStackTrace::ChecksumError(rep.name.clone())));
+            }
+        }
+        match rep.compression {
+            AssetCompression::NONE => Ok(AssetBytes::Mapped(map.clone(), offset, size)),
+            compression => Ok(AssetBytes::Owned(compression.decompress(data).map_err(|e| {corrupt(AssetPackLoadErrorStackTrace::IoError(e))})?))
+        }
+    }
+
+    // we need to preload shaders in order to ensure that we have the shaders ready for includes!!
+    pub fn register_shaders(&self) {
+        let mut to_visit = vec![(self.rep.name.clone(), &self.rep)];
+        while let Some((rep_path, rep)) = to_visit.pop() {
+            if let Some(next) = rep.next.as_ref() {
+                to_visit.extend(next.values().map(|r| {(rep_path.clone() + "/" + r.name.as_str(), r)}));
+            }
+            if !rep.is_file() || rep.meta_data.get("type").map(|t| {t.as_str()}) != Some("Shader") {
+                continue;
+            }
+            let data = self.read_data(rep).expect("Failed to read shader from asset pack!!");
+            RenderPipelineSystem::register_shader_data(rep.name.clone(), "ASSET:".to_string() + rep_path.as_str(), data.to_vec());
         }
     }
 
@@ -427,15 +837,15 @@ pub struct AssetFolder {
 
 impl AssetFolder {
 
-    pub fn load(path: PathBuf) -> Self {
+    // Reads the metadata of every file in the folder. Only failing to read the folder itself is an error, any files or
+    // folders inside it that can't be read are skipped (with a message saying why)
+    pub fn load(path: PathBuf) -> Result<Self, AssetLoadError> {
         // we will go through the directory recfursively and gathering the metadata for all the files
-        let mut read_dir = path.read_dir().expect("Failed to read directory.");
+        let mut read_dir = path.read_dir().map_err(|e| {AssetLoadError::Io(path.display().to_string(), e)})?;
         let mut paths = vec![(read_dir.enumerate(), false, path.clone(), HashMap::<String, PathRep>::new())];
         let mut is_finished = false;
         let mut path_rep = PathRep::new(
-            String::from(path.clone().file_name()
-                .expect("Failed to get directory filename")
-                .to_str().expect("Failed to convert OSString")), 
+            path.file_name().map(|n| {n.to_string_lossy().to_string()}).unwrap_or(path.display().to_string()),
             PathType::DIRECTORY, 
             None
         );
@@ -450,22 +860,38 @@ impl AssetFolder {
                     let dir_path = dir.path();
                     if dir_path.is_dir() && !dir_path.is_symlink() {
                         // we will traverse this before continueing
-                        read_dir = dir_path.read_dir().expect("Failed to read directory.");
+                        read_dir = match dir_path.read_dir() {
+                            Ok(read_dir) => read_dir,
+                            Err(e) => {
+                                println!("Skipping folder {}, it can't be read!! {}", dir_path.display(), e);
+                                continue;
+                            }
+                        };
                         paths.push((read_dir.enumerate(), false, dir_path.clone(), HashMap::<String, PathRep>::new()));
                     }
                     else if dir_path.is_file(){
+                        // sidecar metadata is merged into the file it belongs to, so it isn't an asset itself
+                        if dir_path.extension().map(|e| {e == "meta"}).unwrap_or(false) {
+                            continue;
+                        }
                         // we will take note of this file in a hashmap
                         let mut meta_data: HashMap<String, String> = HashMap::<String,String>::new();
 
                         //lets read the file and get some metadata
-                        let file = File::open(dir_path.clone()).expect("Failed to read file");
-                        let file_metadata = file.metadata().expect("Failed to read metadata of the file");
-                        let file_extension = String::from(dir_path.extension().expect("Failed to get file extension").to_str().unwrap());
+                        let (file, file_metadata) = match File::open(dir_path.clone()).and_then(|f| {f.metadata().map(|m| {(f, m)})}) {
+                            Ok(opened) => opened,
+                            Err(e) => {
+                                println!("Skipping {}, it can't be read!! {}", dir_path.display(), e);
+                                continue;
+                            }
+                        };
+                        // files without an extension (like LICENSE or .gitkeep) are just custom assets
+                        let file_extension = dir_path.extension().map(|e| {e.to_string_lossy().to_string()}).unwrap_or_default();
 
                         let file_type = match file_extension.clone().to_lowercase().as_str() {
-                            "png" | "jpeg" | "bmp" | "tga" | "dxt" | "dds" => "Image".to_string(),
+                            "png" | "jpg" | "jpeg" | "bmp" | "tga" | "dxt" | "dds" => "Image".to_string(),
                             "glsl" | "hlsl" | "pfx" | "comp" | "vert" | "frag" | "gdshad" | "fx" | "shad" => "Shader".to_string(),
-                            "obj" | "gltf" | "glb" | "stl" => "Mesh".to_string(),
+                            "obj" | "gltf" | "glb" | "stl" | "ply" => "Mesh".to_string(),
                             "txt" | "json" | "xml" => "Text".to_string(), 
                             _ => "custom".to_string()
                         };
@@ -474,36 +900,53 @@ impl AssetFolder {
                         meta_data.insert("ext".to_string(), file_extension.clone().to_ascii_lowercase());
 
                         match file_type.clone().as_str() {
+                            "Image" if Texture::is_block_compressed(file_extension.to_ascii_lowercase().as_str()) => {
+                                // block compressed images aren't decoded, so we record what the blocks are
+                                let mut header: Vec<u8> = vec![];
+                                let _ = (&file).take(DDS_HEADER_SIZE as u64).read_to_end(&mut header);
+                                let dds = match Texture::parse_dds(header.as_slice()) {
+                                    Ok(dds) => dds,
+                                    Err(e) => {
+                                        println!("Skipping {}, it isn't a DDS file we can load!! {}", dir_path.display(), e);
+                                        continue;
+                                    }
+                                };
+                                meta_data.insert("width".to_string(), dds.width.to_string());
+                                meta_data.insert("height".to_string(), dds.height.to_string());
+                                meta_data.insert("format".to_string(), dds.format.as_str().to_string());
+                                meta_data.insert("srgb".to_string(), dds.srgb.to_string());
+                                meta_data.insert("mips".to_string(), dds.mip_count.to_string());
+                            },
                             "Image" => {
-                                // we will get the image dimensions
+                                // we will get the image dimensions, which only needs the header to be read
                                 let mut o_mage = image::ImageReader::new(BufReader::new(file));
                                 o_mage.set_format(image::ImageFormat::from_extension(file_extension.clone()).expect(format!("Cannot currently load image formats of the type {}", file_extension).as_str()));
-                                let mage = o_mage.decode().expect("Failed to read image!!");
-                                meta_data.insert("width".to_string(), mage.width().to_string());
-                                meta_data.insert("height".to_string(), mage.height().to_string());
+                                let (width, height) = match o_mage.into_dimensions() {
+                                    Ok(dimensions) => dimensions,
+                                    Err(e) => {
+                                        println!("Skipping {}, the image can't be read!! {}", dir_path.display(), e);
+                                        continue;
+                                    }
+                                };
+                                meta_data.insert("width".to_string(), width.to_string());
+                                meta_data.insert("height".to_string(), height.to_string());
                                 meta_data.insert("depth".to_string(), "32".to_string());
                             },
-                            "Shader" => {
-                                // we need to preload this in order to ensure that we have the shaders ready!!
-                                let shader_name = dir_path.file_name().unwrap().to_str().unwrap();
-                                let relative = dir_path.strip_prefix(path.clone()).unwrap().to_path_buf();
-                                let asset_path = "ASSET:".to_string() + path.file_stem().unwrap().to_str().unwrap() + "/" + relative.to_str().unwrap();
-                                let mut file = File::open(dir_path.clone()).unwrap();
-                                let mut data: Vec<u8> = vec![];
-                                let _ = file.read_to_end(&mut data);
-                                RenderPipelineSystem::register_shader_data(String::from(shader_name), asset_path, data);
-                            }
                             _ => {}
                         };
 
+                        // a <file>.meta next to the asset can add to or override any of the metadata (e.g. author:...)
+                        if let Some(sidecar) = AssetFolder::read_meta_file(&dir_path) {
+                            meta_data.extend(sidecar);
+                        }
 
-
-                        let mut rep = PathRep::new(String::from(dir_path.file_name().unwrap().to_str().unwrap()), PathType::FILE, Some(meta_data));
+                        let file_name = dir_path.file_name().map(|n| {n.to_string_lossy().to_string()}).unwrap_or_default();
+                        let mut rep = PathRep::new(file_name.clone(), PathType::FILE, Some(meta_data));
                         
                         #[cfg(target_os="windows")]rep.set_data_size(file_metadata.file_size());
                         #[cfg(target_os="linux")]rep.set_data_size(file_metadata.size());
                         rep.set_file_path(dir_path.clone());
-                        current.3.insert(String::from(dir_path.file_name().unwrap().to_str().unwrap()), rep);
+                        current.3.insert(file_name, rep);
                         
                     }
                 }
@@ -514,7 +957,7 @@ impl AssetFolder {
                     // we will pop the last path and add it the the previous pathrep
                     let dir = paths.pop().expect("Error getting from paths list");
 
-                    let mut rep = PathRep::new(String::from(dir.2.file_name().unwrap().to_str().unwrap()), PathType::DIRECTORY, None);
+                    let mut rep = PathRep::new(dir.2.file_name().map(|n| {n.to_string_lossy().to_string()}).unwrap_or_default(), PathType::DIRECTORY, None);
                     rep.set_next(dir.3);
                     
 
@@ -531,30 +974,295 @@ impl AssetFolder {
 
         }
 
-        Self { directory_location: path, rep: path_rep }
+        Ok(Self { directory_location: path, rep: path_rep })
 
     }
 
+    /** Reads the sidecar metadata of a file (e.g. brick.png.meta for brick.png), if there is one.
+    * This can either be a JSON object:
+    *     { "author": "me", "compression": "none" }
+    * or one key:value (or key=value) pair per line, where lines starting with # are ignored:
+    *     author: me
+    *     compression: none
+    */
+    fn read_meta_file(path: &PathBuf) -> Option<HashMap<String, String>> {
+        let mut meta_path = path.clone().into_os_string();
+        meta_path.push(".meta");
+        let text = std::fs::read_to_string(PathBuf::from(meta_path)).ok()?;
+        let mut meta_data = HashMap::<String, String>::new();
+
+        if text.trim_start().starts_with('{') {
+            let value = ValueBuilder::new().from_str(text.as_str()).build();
+            let entries = match value {
+                Value::Array(arr) => arr,
+                Value::Component(..) => vec![value],
+                _ => vec![]
+            };
+            for entry in entries {
+                if let Some((key, v)) = entry.as_component() {
+                    let v = match &*v {
+                        Value::String(s) => s.clone(),
+                        Value::I32(i) => i.to_string(),
+                        Value::F32(f) => f.to_string(),
+                        _ => continue
+                    };
+                    meta_data.insert(key, v);
+                }
+            }
+        }
+        else {
+            for line in text.lines().map(|l| {l.trim()}).filter(|l| {!l.is_empty() && !l.starts_with('#')}) {
+                let pair = match (line.find(':'), line.find('=')) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b)
+                };
+                if let Some(i) = pair {
+                    meta_data.insert(line[..i].trim().to_string(), line[i + 1..].trim().to_string());
+                }
+            }
+        }
+        Some(meta_data)
+    }
+
+    // gives back every file in the folder along with its path relative to the folder
+    pub fn entries(&self) -> Vec<(String, &PathRep)> {
+        let mut files: Vec<(String, &PathRep)> = vec![];
+        let mut to_visit = vec![(String::new(), &self.rep)];
+        while let Some((rep_path, rep)) = to_visit.pop() {
+            if rep.is_file() {
+                files.push((rep_path, rep));
+            }
+            else if let Some(next) = rep.next.as_ref() {
+                let prefix = if rep_path.is_empty() { String::new() } else { rep_path + "/" };
+                to_visit.extend(next.values().map(|r| {(prefix.clone() + r.name.as_str(), r)}));
+            }
+        }
+        files
+    }
+
+    // gives back the asset path (ASSET:folder/...) and the physical path of every file in the folder
+    pub fn asset_files(&self) -> Vec<(String, PathBuf)> {
+        let mut files = vec![];
+        let mut to_visit = vec![("ASSET:".to_string() + self.rep.name.as_str(), &self.rep)];
+        while let Some((rep_path, rep)) = to_visit.pop() {
+            if let Some(next) = rep.next.as_ref() {
+                to_visit.extend(next.values().map(|r| {(rep_path.clone() + "/" + r.name.as_str(), r)}));
+            }
+            if let Some(file_path) = rep.get_file_path() {
+                files.push((rep_path, file_path));
+            }
+        }
+        files
+    }
+
+    // we need to preload shaders in order to ensure that we have the shaders ready for includes!!
+    // This is kept out of load so that folders can be read without the render pipeline (e.g. when building asset packs)
+    pub fn register_shaders(&self) {
+        let mut to_visit = vec![&self.rep];
+        while let Some(rep) = to_visit.pop() {
+            if let Some(next) = rep.next.as_ref() {
+                to_visit.extend(next.values());
+            }
+            if !rep.is_file() || rep.meta_data.get("type").map(|t| {t.as_str()}) != Some("Shader") {
+                continue;
+            }
+            let dir_path = rep.get_file_path().expect("No file path associated with this path!! This is a bug!!");
+            let relative = dir_path.strip_prefix(self.directory_location.clone()).unwrap().to_path_buf();
+            let asset_path = "ASSET:".to_string() + self.rep.name.as_str() + "/" + relative.to_str().unwrap();
+            let mut file = File::open(dir_path.clone()).unwrap();
+            let mut data: Vec<u8> = vec![];
+            let _ = file.read_to_end(&mut data);
+            RenderPipelineSystem::register_shader_data(rep.name.clone(), asset_path, data);
+        }
+    }
+
+}
+
+// The bytes of a loaded asset. Uncompressed assets in packs are mapped straight from disk instead of being read into
+// memory, so use `as_slice` (or deref) instead of relying on this being a Vec!!
+pub enum AssetBytes {
+    Owned(Vec<u8>),
+    Mapped(Arc<Mmap>, usize, usize),// the map, the offset and the size of the asset within it
+}
+
+impl AssetBytes {
+
+    pub fn as_slice(&self) -> &[u8] {
+        match self {
+            Self::Owned(data) => data.as_slice(),
+            Self::Mapped(map, offset, size) => &map[*offset..*offset + *size],
+        }
+    }
 }
 
+impl Deref for AssetBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+// Where a located asset's data lives, so that it can be read without holding onto the asset manager
+enum AssetSource {
+    Pack(Arc<Mmap>, String),// the mapped pack and its location
+    File(PathBuf),
+}
+
+// A handle to an asset that is being loaded on the asset loader threads
+pub struct AssetHandle<T> where T : AssetResource {
+    path: String,
+    state: Arc<(Mutex<AssetHandleState<T>>, Condvar)>,
+}
+
+struct AssetHandleState<T> where T : AssetResource {
+    result: Option<Result<T, AssetLoadError>>,
+    callback: Option<Box<dyn FnOnce(&AssetHandle<T>) + Send>>,
+}
+
+impl<T> AssetHandle<T> where T : AssetResource {
+
+    pub fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.state.0.lock().result.is_some()
+    }
+
+    // blocks until the asset has finished loading
+    pub fn wait(self) -> Result<T, AssetLoadError> {
+        let (lock, condvar) = &*self.state;
+        let mut state = lock.lock();
+        while state.result.is_none() {
+            condvar.wait(&mut state);
+        }
+        state.result.take().unwrap()
+    }
+
+    // gets the asset if it has finished loading, without blocking
+    pub fn try_take(&self) -> Option<Result<T, AssetLoadError>> {
+        self.state.0.lock().result.take()
+    }
+
+    // calls the callback on the loader thread once the asset has finished loading, or straight away if it already has.
+    // The handle isn't locked while the callback runs, so it can take the result out of the handle it is given
+    pub fn on_ready<F>(&self, callback: F) where F : FnOnce(&AssetHandle<T>) + Send + 'static {
+        let mut state = self.state.0.lock();
+        if state.result.is_none() {
+            state.callback = Some(Box::new(callback));
+            return;
+        }
+        drop(state);
+        callback(self);
+    }
+}
+
+static ASSET_LOADER: Lazy<ThreadPool> = Lazy::new(|| {
+    ThreadPool::builder().name_prefix("asset-loader-").create().expect("Failed to create the asset loader threads!!")
+});
+
 pub struct AssetData {
 
     pub asset_name: String,
     pub asset_path: String,
-    pub data: Vec<u8>,
+    pub data: AssetBytes,
     pub metadata: HashMap<String, String>,
 }
 
+// An asset pack or folder that has been mounted in the asset manager
+pub enum MountedAssets {
+    Pack(AssetPack),
+    Folder(AssetFolder),
+}
+
+impl MountedAssets {
+
+    pub fn get_name(&self) -> String {
+        self.rep().name.clone()
+    }
+
+    fn rep(&self) -> &PathRep {
+        match self {
+            Self::Pack(pack) => &pack.rep,
+            Self::Folder(folder) => &folder.rep,
+        }
+    }
+
+    fn source(&self, rep: &PathRep) -> AssetSource {
+        match self {
+            Self::Pack(pack) => AssetSource::Pack(pack.map.clone(), pack.asset_location.clone()),
+            // we should have saved the file path when we first traversed the folder!!
+            Self::Folder(_) => AssetSource::File(rep.get_file_path().expect("No file path associated with this path!! This is a bug!!")),
+        }
+    }
+}
+
 pub struct AssetManager {
 
-    asset_packs: HashMap<String, AssetPack>,
-    asset_folders: HashMap<String, AssetFolder>,// changed this to asset folders so as to allow developers to not have to use asset packs
-                                            // and to make it easier to implement asset pack creation from folders
+    mounts: Vec<MountedAssets>,// asset packs and folders can both be mounted so that developers don't have to use asset packs
+    // Mounts with the same name are layered, so a later mount (e.g. a mod or a patch pack) shadows the files of earlier ones
+    // with the same path, while the rest of the files still come from the earlier mounts
     asset_data_reference: HashMap<String, Arc<AssetData>>, // we want this to be read only, as we do not want to edit the original assetpack!!,
     // If you want to edit files, then you must access them through the file system
     // This will mean that you will not be able to load them through the asset manager
     // and have to manage them yourself!!
+    asset_last_used: HashMap<String, u64>,// when each cached asset was last loaded, used for LRU eviction
+    use_tick: u64,
+    memory_budget: Option<usize>,// the most bytes of asset data we want to keep cached, None to never evict automatically
+    watched_files: HashMap<String, (PathBuf, Option<SystemTime>, u64)>,// the files of the asset folders along with when they were last modified and their size
+    live_resources: HashMap<String, Vec<Box<dyn FnMut() -> bool + Send>>>,// updates resources when their asset changes, giving back false once the resource is dropped
+    reload_hooks: HashMap<String, Box<dyn FnMut() -> bool + Send>>,// reloads what an asset made in the render system (e.g. a texture's image), there's only one of these for each asset
+    hot_reload: Option<(Duration, Instant)>,// how often we check the asset folders for changes and when we last did
+    dependencies: HashMap<String, Vec<String>>,// the assets that each asset loaded while it was being initialised
+
+}
+
+thread_local! {
+    // the assets that are being initialised on this thread, so that we know which asset loaded which
+    static INITIALISING: RefCell<Vec<String>> = RefCell::new(vec![]);
+}
+
+/** A list of assets, e.g. everything that a level needs. This is just one asset path per line, where lines starting with # are ignored:
+*     # level 1
+*     ASSET:assets/meshes/house.obj
+*     ASSET:assets/images/brick.png
+* A manifest only needs to list the roots of a level, `AssetManager::expand_manifest` will add everything that they depend on.
+*/
+#[derive(Clone, Debug, Default)]
+pub struct AssetManifest {
+    pub assets: Vec<String>,
+}
+
+impl AssetManifest {
 
+    pub fn parse(text: &str) -> Self {
+        let assets = text.lines().map(|l| {l.trim()})
+            .filter(|l| {!l.is_empty() && !l.starts_with('#')})
+            .map(|l| {l.to_string()})
+            .collect();
+        Self { assets }
+    }
+
+    // reads a manifest from an asset (ASSET:...) or from a file on disk
+    pub fn load(path: String) -> Result<Self, AssetLoadError> {
+        if path.starts_with("ASSET:") {
+            let data = AssetManager::get_asset_data(path)?;
+            Ok(Self::parse(String::from_utf8_lossy(data.data.as_slice()).as_ref()))
+        }
+        else {
+            let text = std::fs::read_to_string(&path).map_err(|e| {AssetLoadError::Io(path.clone(), e)})?;
+            Ok(Self::parse(text.as_str()))
+        }
+    }
+
+    pub fn save(&self, path: PathBuf) -> Result<(), io::Error> {
+        let mut text = String::new();
+        for asset in &self.assets {
+            text = text + asset.as_str() + "\n";
+        }
+        std::fs::write(path, text)
+    }
 }
 
 
@@ -562,163 +1270,542 @@ impl AssetManager {
 
     pub fn new() -> Self{
         Self{
-            asset_packs: HashMap::new(),
-            asset_folders: HashMap::new(),
-            asset_data_reference: HashMap::new()
+            mounts: vec![],
+            asset_data_reference: HashMap::new(),
+            asset_last_used: HashMap::new(),
+            use_tick: 0,
+            memory_budget: None,
+            watched_files: HashMap::new(),
+            live_resources: HashMap::new(),
+            reload_hooks: HashMap::new(),
+            // we only really want this while developing, so it's off by default in release builds
+            hot_reload: if cfg!(debug_assertions) { Some((Duration::from_millis(500), Instant::now())) } else { None },
+            dependencies: HashMap::new(),
+        }
+    }
+
+    // Records that one asset needs another. This is done for you when an asset loads another asset in its init,
+    // but anything that pulls in other assets some other way (e.g. shader includes) should call this
+    pub fn add_dependency(from: String, to: String) {
+        if from == to {
+            return;
+        }
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let mut asset_mg = p_asset_mg.lock();
+            let dependencies = asset_mg.dependencies.entry(from).or_insert(vec![]);
+            if !dependencies.contains(&to) {
+                dependencies.push(to);
+            }
+        }
+    }
+
+    // The assets that this asset loaded directly
+    pub fn dependencies_of(path: String) -> Vec<String> {
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let asset_mg = p_asset_mg.lock();
+            asset_mg.dependencies.get(&path).cloned().unwrap_or(vec![])
+        }
+    }
+
+    // Everything that this asset needs, including what its dependencies need
+    pub fn all_dependencies_of(path: String) -> Vec<String> {
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let asset_mg = p_asset_mg.lock();
+            let mut out: Vec<String> = vec![];
+            let mut to_visit = vec![path.clone()];
+            while let Some(current) = to_visit.pop() {
+                for dependency in asset_mg.dependencies.get(&current).into_iter().flatten() {
+                    if *dependency != path && !out.contains(dependency) {
+                        out.push(dependency.clone());
+                        to_visit.push(dependency.clone());
+                    }
+                }
+            }
+            out
         }
     }
 
-    pub fn load_asset_pack(full_path: String){
+    // Adds everything that the assets in the manifest depend on, as far as we know from what has been loaded so far.
+    // Load a level once and save the expanded manifest to be able to preload or pack the whole level next time
+    pub fn expand_manifest(manifest: &AssetManifest) -> AssetManifest {
+        let mut assets = manifest.assets.clone();
+        for root in &manifest.assets {
+            for dependency in AssetManager::all_dependencies_of(root.clone()) {
+                if !assets.contains(&dependency) {
+                    assets.push(dependency);
+                }
+            }
+        }
+        AssetManifest { assets }
+    }
+
+    // Reads in the data of everything in the manifest (and what it depends on) so that loading them later doesn't touch the disk
+    pub fn preload_manifest(manifest: &AssetManifest) -> Result<(), AssetLoadError> {
+        for path in AssetManager::expand_manifest(manifest).assets {
+            AssetManager::get_asset_data(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn load_asset_pack(full_path: String) -> Result<(), AssetLoadError> {
+        let asset_pack_path = PathBuf::from(full_path.clone());
+        let name = asset_pack_path.file_stem().unwrap().to_str().unwrap().to_string();
+        AssetManager::mount_asset_pack(name, full_path)
+    }
+
+    // loads an asset pack so that it can be accessed with ASSET:<name>/...
+    pub fn mount_asset_pack(name: String, full_path: String) -> Result<(), AssetLoadError> {
         unsafe{
             let p_this = Env::get_asset_mgr();
             let asset_pack_path = PathBuf::from(full_path);
-            let asset_pack = AssetPack::load(asset_pack_path);
+            let mut asset_pack = AssetPack::try_load(asset_pack_path)?;
+            asset_pack.rep.name = name;
+            asset_pack.register_shaders();
             let mut this = p_this.lock();
-            this.asset_packs.insert(asset_pack.asset_location.clone(), asset_pack);
+            this.push_mount(MountedAssets::Pack(asset_pack));
             drop(this);
         }
+        Ok(())
     }
 
-    pub fn load_asset_folder(full_path: String){
+    pub fn load_asset_folder(full_path: String) -> Result<(), AssetLoadError> {
+        let asset_pack_path = PathBuf::from(full_path.clone());
+        let temp = asset_pack_path.file_name().unwrap();
+        let temp2 = PathBuf::from(temp.to_str().unwrap());
+        let temp3 = temp2.file_stem().unwrap();
+        let temp4 = temp3.to_str().unwrap();
+        AssetManager::mount_asset_folder(temp4.to_string(), full_path)
+    }
+
+    // loads an asset folder so that it can be accessed with ASSET:<name>/...
+    pub fn mount_asset_folder(name: String, full_path: String) -> Result<(), AssetLoadError> {
         unsafe{
             let p_this = Env::get_asset_mgr();
             let asset_pack_path = PathBuf::from(full_path);
+            let mut asset_folder = AssetFolder::load(asset_pack_path)?;
             let mut this = p_this.lock();
-            let temp = asset_pack_path.file_name().unwrap();
-            let temp2 = PathBuf::from(temp.to_str().unwrap());
-            let temp3 = temp2.file_stem().unwrap();
-            let temp4 = temp3.to_str().unwrap();
-            let asset_folder = AssetFolder::load(asset_pack_path);
-            this.asset_folders.insert(String::from_str(temp4).unwrap(), asset_folder);
+            asset_folder.rep.name = name.clone();
+            asset_folder.register_shaders();
+            for (asset_path, file_path) in asset_folder.asset_files() {
+                let (modified, size) = AssetManager::file_stamp(&file_path).unwrap_or((None, 0));
+                this.watched_files.insert(asset_path, (file_path, modified, size));
+            }
+            this.push_mount(MountedAssets::Folder(asset_folder));
         }
+        Ok(())
     }
 
-    pub fn load_asset<T>(path: String) -> T where T : AssetResource {
-        // first we check if the asset has already been loaded
-        unsafe {
-            let p_asset_mg = Env::get_asset_mgr();
-            let mut asset_mg = p_asset_mg.lock();
-            
+    fn push_mount(&mut self, mount: MountedAssets) {
+        // anything we have already loaded from this mount name may now be shadowed, so it needs to be read again
+        let prefix = "ASSET:".to_string() + mount.get_name().as_str() + "/";
+        self.asset_data_reference.retain(|p, _| {!p.starts_with(&prefix)});
+        self.mounts.push(mount);
+    }
 
-            let mut asset = T::new();
+    // splits ASSET:name/path/to/file into the mount name and the rest of the path
+    fn split_asset_path(path: &String) -> Result<(&str, Vec<&str>), AssetLoadError> {
+        let path_string = path.strip_prefix("ASSET:").ok_or(AssetLoadError::UnknownPath(path.clone()))?;
+        let mut path_list = path_string.split('/').filter(|s| {!s.is_empty()});
+        let name = path_list.next().ok_or(AssetLoadError::UnknownPath(path.clone()))?;
+        Ok((name, path_list.collect()))
+    }
 
-            // find the asset and read the data in!!
-            // if we are in debug, then we will try to use the debug reference if the asset is not loaded already
+    // Gives back whether there is a file or directory at the path in any of the mounts
+    pub fn exists(path: String) -> bool {
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let asset_mg = p_asset_mg.lock();
+            let (name, path_list) = match AssetManager::split_asset_path(&path) {
+                Ok(split) => split,
+                Err(_) => return false
+            };
+            asset_mg.mounts.iter().any(|m| {m.get_name() == name && m.rep().find(&path_list).is_some()})
+        }
+    }
 
-            let mut asset_data: Option<Arc<AssetData>> = None;
-            if let Some(pre_loaded_asset) = asset_mg.asset_data_reference.get(&path) {
-                
-                let ref_asset_data = pre_loaded_asset;
-                asset_data = Some(ref_asset_data.clone());
+    // Lists what is in a directory across all of the mounts, e.g. list("ASSET:assets/textures") gives back
+    // ASSET:assets/textures/brick.png etc. Directories end with a / so that you can tell them apart from files
+    pub fn list(dir: String) -> Result<Vec<String>, AssetLoadError> {
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let asset_mg = p_asset_mg.lock();
+            let (name, path_list) = AssetManager::split_asset_path(&dir)?;
+            let mut prefix = "ASSET:".to_string() + name;
+            for part in &path_list {
+                prefix = prefix + "/" + part;
+            }
 
+            let mut known = false;
+            let mut found = false;
+            let mut entries = std::collections::BTreeSet::<String>::new();
+            for mount in asset_mg.mounts.iter().filter(|m| {m.get_name() == name}) {
+                known = true;
+                if let Some(next) = mount.rep().find(&path_list).and_then(|r| {r.next.as_ref()}) {
+                    found = true;
+                    entries.extend(next.values().map(|r| {
+                        prefix.clone() + "/" + r.name.as_str() + if r.is_file() { "" } else { "/" }
+                    }));
+                }
+            }
+            if !known {
+                return Err(AssetLoadError::UnknownPack(name.to_string()));
             }
-            else{
+            if !found {
+                return Err(AssetLoadError::UnknownPath(dir.clone()));
+            }
+            Ok(entries.into_iter().collect())
+        }
+    }
 
-                // first lets get the asset pack that we need
-                // This will need to be the first directory in the path
-                // e.g. ASSET:pack/...
-                let path_string: String = path[6..].to_string();
-                let pack_name: String = path_string[..(path_string.find('/').unwrap_or_else(|| {path_string.len()}))].to_string();
+    fn file_stamp(file_path: &PathBuf) -> Option<(Option<SystemTime>, u64)> {
+        let meta = std::fs::metadata(file_path).ok()?;
+        Some((meta.modified().ok(), meta.len()))
+    }
 
-                let packs_result = asset_mg.asset_packs.get(&pack_name);
-                let folders_result = asset_mg.asset_folders.get(&pack_name);
+    // Sets how often the asset folders are checked for changes, or None to turn hot reloading off
+    pub fn set_hot_reload(interval: Option<Duration>) {
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let mut asset_mg = p_asset_mg.lock();
+            asset_mg.hot_reload = interval.map(|i| {(i, Instant::now())});
+        }
+    }
 
-                // now we traverse through the pathrep
-                if let Some(result) = packs_result {
-                    let mut temp = &result.rep;
-                    let mut path_list = path_string.as_str().rsplit('/').collect::<VecDeque<&str>>();
-                    
-                    while !temp.is_file(){
-                        if path_list.len() == 0 {
-                            panic!("We couldn't find the asset!!! This could be a serious bug, please report this!!")
-                        }
-                        if temp.name == path_list[0] {
-                            path_list.pop_front();
-                            temp = {
-                                let t = temp.next.as_ref().expect("not a directory");
-                                let mut out: Option<&PathRep> = None;
-                                for entry in t {
-                                    if entry.0 == path_list[0] {
-                                        out = Some(entry.1);
-                                        break;
-                                    }
-                                }
-                                out
-                            }.expect(format!("Failed to find next path {}", path_list[0]).as_str());
-                        }
-                        
+    // Calls `AssetResource::update` on the resource whenever its asset changes on disk.
+    // Only a weak reference is kept, so this won't keep the resource alive!!
+    pub fn watch_asset<T>(path: String, resource: &Arc<Mutex<T>>) where T : AssetResource + Send + 'static {
+        let weak = Arc::downgrade(resource);
+        let asset_path = path.clone();
+        let update = move || {
+            match weak.upgrade() {
+                Some(p_resource) => {
+                    if let Err(e) = p_resource.lock().update() {
+                        println!("Failed to reload {}: {}", asset_path, e);
                     }
+                    true
+                },
+                None => false
+            }
+        };
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let mut asset_mg = p_asset_mg.lock();
+            asset_mg.live_resources.entry(path).or_insert(vec![]).push(Box::new(update));
+        }
+    }
 
-                    // then after we have found the file, we must load it!!
-                    // lets get the asset pack file and read the specific data
+    /** Sets what happens when an asset changes on disk, for the things that are shared between every resource loaded
+    * from the asset (like the image a texture registers with the render system) so don't belong to any one of them.
+    * Setting it again replaces the old one, so it is safe to call from AssetResource::init. The hook gives back false
+    * once there's nothing left for it to update.
+    */
+    pub fn set_reload_hook(path: String, hook: Box<dyn FnMut() -> bool + Send>) {
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let mut asset_mg = p_asset_mg.lock();
+            asset_mg.reload_hooks.insert(path, hook);
+        }
+    }
+
+    // this should be called every frame, it will only check for changes once the hot reload interval has passed
+    pub fn processing() {
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let mut asset_mg = p_asset_mg.lock();
+            match asset_mg.hot_reload.as_mut() {
+                Some((interval, last)) if last.elapsed() >= *interval => {
+                    *last = Instant::now();
+                },
+                _ => return
+            }
+        }
+        AssetManager::poll_changes();
+    }
+
+    // Checks the files of the asset folders for changes, refreshing their data and updating any watched resources.
+    // Gives back the asset paths that changed. Files added after the folder was loaded are not picked up!!
+    pub fn poll_changes() -> Vec<String> {
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let mut asset_mg = p_asset_mg.lock();
 
+            let mut changed = vec![];
+            for (asset_path, (file_path, modified, size)) in asset_mg.watched_files.iter_mut() {
+                // the file may be in the middle of being saved, so we'll just catch it next time
+                if let Some(stamp) = AssetManager::file_stamp(file_path) {
+                    if stamp != (*modified, *size) {
+                        (*modified, *size) = stamp;
+                        changed.push(asset_path.clone());
+                    }
                 }
-                else if let Some(result) = folders_result {
-                    let mut temp = &result.rep;
-                    let mut path_list = path_string.as_str().split('/').collect::<VecDeque<&str>>();
-                    
-                    while !temp.is_file(){
-                        if path_list.len() == 0 {
-                            panic!("We couldn't find the asset!!! This could be a serious bug, please report this!!")
-                        }
-                        if temp.name == path_list[0] {
-                            path_list.pop_front();
-                            temp = {
-                                let t = temp.next.as_ref().expect("not a directory");
-                                let mut out: Option<&PathRep> = None;
-                                for entry in t {
-                                    if entry.0 == path_list[0] {
-                                        out = Some(entry.1);
-                                        break;
-                                    }
-                                }
-                                out
-                            }.expect(format!("Failed to find next path {}", path_list[0]).as_str());
+            }
+
+            for asset_path in &changed {
+                let (rep, source) = match asset_mg.locate_asset(asset_path) {
+                    Ok(located) => located,
+                    Err(_) => continue
+                };
+                // shaders are read from the render pipeline system, so that needs the new source too
+                if rep.meta_data.get("type").map(|t| {t.as_str()}) == Some("Shader") {
+                    if let AssetSource::File(file_path) = &source {
+                        if let Ok(data) = std::fs::read(file_path) {
+                            RenderPipelineSystem::register_shader_data(rep.name.clone(), asset_path.clone(), data);
                         }
-                        
                     }
-                    // lets load the file
-                    // we should have saved it when we first traversed it!!
-                    let file = File::open(temp.get_file_path().expect("No file path associated with this path!! This is a bug!!")).expect("Failed to open the asset file!!");
-                    let mut reader = BufReader::new(file);
-                    let mut data: Vec<u8> = Vec::<u8>::new();
-                    if temp.meta_data["type"] != "Shader".to_string() {// we don't want to read shaders from here!! They will be pre loaded by the render pipeline system
-                        let _ = reader.read_to_end(&mut data);
+                }
+                if asset_mg.asset_data_reference.contains_key(asset_path) {
+                    match AssetManager::read_asset_data(asset_path, &rep, &source) {
+                        Ok(d) => {
+                            asset_mg.asset_data_reference.insert(asset_path.clone(), Arc::new(d));
+                        },
+                        Err(e) => println!("Failed to reload {}: {}", asset_path, e)
                     }
-                    let d = Arc::new(
-                        AssetData {
-                            asset_name: temp.name.clone(),
-                            asset_path: path.clone(),
-                            data: data,
-                            metadata: temp.meta_data.clone()
-                        }
-                    );
-                    asset_mg.asset_data_reference.insert(path.clone(), d.clone());
+                }
+            }
 
-                    asset_data = Some(
-                        d.clone()
-                    );
+            // the resources may need to load assets while updating, so we can't hold onto the asset manager
+            let mut live = changed.iter()
+                .filter_map(|p| {asset_mg.live_resources.remove(p).map(|r| {(p.clone(), r)})})
+                .collect::<Vec<(String, Vec<Box<dyn FnMut() -> bool + Send>>)>>();
+            let mut hooks = changed.iter()
+                .filter_map(|p| {asset_mg.reload_hooks.remove(p).map(|h| {(p.clone(), h)})})
+                .collect::<Vec<(String, Box<dyn FnMut() -> bool + Send>)>>();
+            drop(asset_mg);
+            // the shared data goes first, so that the resources see the new version when they update
+            hooks.retain_mut(|(_, hook)| {hook()});
+            for (_, resources) in live.iter_mut() {
+                resources.retain_mut(|update| {update()});
+            }
+
+            let mut asset_mg = p_asset_mg.lock();
+            for (asset_path, resources) in live {
+                if !resources.is_empty() {
+                    asset_mg.live_resources.entry(asset_path).or_insert(vec![]).extend(resources);
                 }
-                
+            }
+            for (asset_path, hook) in hooks {
+                // a new hook may have been set while this one was running
+                asset_mg.reload_hooks.entry(asset_path).or_insert(hook);
+            }
+            changed
+        }
+    }
+
+    // loads an asset, panicking if it can't be found or read. Use try_load_asset if you want to handle the error yourself!!
+    pub fn load_asset<T>(path: String) -> T where T : AssetResource {
+        match AssetManager::try_load_asset(path) {
+            Ok(asset) => asset,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    pub fn try_load_asset<T>(path: String) -> Result<T, AssetLoadError> where T : AssetResource {
+        // if we are being loaded by another asset, then that asset depends on us
+        if let Some(parent) = INITIALISING.with(|i| {i.borrow().last().cloned()}) {
+            AssetManager::add_dependency(parent, path.clone());
+        }
+        let asset_data = AssetManager::get_asset_data(path.clone())?;
+
+        // now that we have the data, we can pass it along to the asset
+        // the asset manager is unlocked by now so that assets can load other assets in init
+        let mut asset = T::new();
+        INITIALISING.with(|i| {i.borrow_mut().push(path)});
+        let result = asset.init(asset_data);
+        INITIALISING.with(|i| {i.borrow_mut().pop()});
+        result.map(|_| {asset})
+    }
 
+    // gets the data of an asset, reading it in if it hasn't been loaded already
+    pub fn get_asset_data(path: String) -> Result<Arc<AssetData>, AssetLoadError> {
+        // first we check if the asset has already been loaded
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let mut asset_mg = p_asset_mg.lock();
+
+            asset_mg.use_tick += 1;
+            let tick = asset_mg.use_tick;
+            asset_mg.asset_last_used.insert(path.clone(), tick);
+
+            if let Some(pre_loaded_asset) = asset_mg.asset_data_reference.get(&path) {
+                Ok(pre_loaded_asset.clone())
+            }
+            else {
+                let located = asset_mg.locate_asset(&path);
+                // we don't want to hold onto the asset manager while reading the file!!
+                drop(asset_mg);
+                let read = located.and_then(|(rep, source)| {AssetManager::read_asset_data(&path, &rep, &source)});
+
+                let mut asset_mg = p_asset_mg.lock();
+                let d = match read {
+                    Ok(d) => Arc::new(d),
+                    Err(e) => {
+                        asset_mg.asset_last_used.remove(&path);
+                        return Err(e);
+                    }
+                };
                 // when we have loaded it, we must add it into the preloaded asset list so we can reference it again when we need to use it again
-                // unless we have been asked to delist it for memory consuption minimisation (we will only do this for items that need to be edited, 
-                // so must have an unique memory emtry)
-                    
+                // someone else may have loaded it while we were reading, in which case we use theirs
+                let d = asset_mg.asset_data_reference.entry(path.clone()).or_insert(d).clone();
+                asset_mg.enforce_memory_budget();
+                Ok(d)
+            }
+        }
+    }
 
-                
+    // Loads an asset on the asset loader threads, so that level loads don't stall the main loop
+    pub fn load_asset_async<T>(path: String) -> AssetHandle<T> where T : AssetResource + Send + 'static {
+        let state = Arc::new((Mutex::new(AssetHandleState { result: None, callback: None }), Condvar::new()));
+        let handle = AssetHandle { path: path.clone(), state: state.clone() };
+        ASSET_LOADER.spawn_ok(async move {
+            let result = AssetManager::try_load_asset::<T>(path.clone());
+            let (lock, condvar) = &*state;
+            let mut guard = lock.lock();
+            let callback = guard.callback.take();
+            guard.result = Some(result);
+            condvar.notify_all();
+            drop(guard);
+            // the result is already in the handle, so the callback can use it without blocking anyone else
+            if let Some(callback) = callback {
+                callback(&AssetHandle { path, state: state.clone() });
             }
-            
-            // now that we have the data, we can pass it along to the asset
+        });
+        handle
+    }
 
+    // Drops the cached data of an asset so that it will be read again next time it is loaded.
+    // Anything still holding onto the data will keep it alive, so make sure you unload your asset resources too!!
+    pub fn unload_asset(path: String) -> bool {
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let mut asset_mg = p_asset_mg.lock();
+            asset_mg.asset_last_used.remove(&path);
+            asset_mg.asset_data_reference.remove(&path).is_some()
+        }
+    }
 
+    // Unloads an asset resource and drops its cached data if nothing else is using it
+    pub fn release_asset<T>(asset: &mut T, path: String) where T : AssetResource {
+        asset.unload();
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let mut asset_mg = p_asset_mg.lock();
+            if asset_mg.asset_data_reference.get(&path).map(|d| {Arc::strong_count(d) == 1}).unwrap_or(false) {
+                asset_mg.asset_data_reference.remove(&path);
+                asset_mg.asset_last_used.remove(&path);
+            }
+        }
+    }
 
-            asset.init(asset_data.expect(format!("Failed to load an asset. Are you sure that the asset folder has been exposed? asset:{}", path.clone()).as_str()));
+    // Evicts every cached asset that is only being held by the asset manager, returning how many were evicted
+    pub fn collect_unused() -> usize {
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let mut asset_mg = p_asset_mg.lock();
+            let unused = asset_mg.asset_data_reference.iter()
+                .filter(|(_, d)| {Arc::strong_count(d) == 1})
+                .map(|(p, _)| {p.clone()})
+                .collect::<Vec<String>>();
+            for path in &unused {
+                asset_mg.asset_data_reference.remove(path);
+                asset_mg.asset_last_used.remove(path);
+            }
+            unused.len()
+        }
+    }
+
+    // Sets how many bytes of asset data can be cached before the least recently used unused assets are evicted
+    pub fn set_memory_budget(budget: Option<usize>) {
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let mut asset_mg = p_asset_mg.lock();
+            asset_mg.memory_budget = budget;
+            asset_mg.enforce_memory_budget();
+        }
+    }
+
+    pub fn get_cached_size() -> usize {
+        unsafe {
+            let p_asset_mg = Env::get_asset_mgr();
+            let asset_mg = p_asset_mg.lock();
+            asset_mg.cached_size()
+        }
+    }
+
+    fn cached_size(&self) -> usize {
+        self.asset_data_reference.values().map(|d| {d.data.len()}).sum()
+    }
 
+    // evicts the least recently used assets that nothing else is holding onto until we are within the budget
+    // assets that are still in use can't be freed anyway, so they are skipped
+    fn enforce_memory_budget(&mut self) {
+        let budget = match self.memory_budget {
+            Some(b) => b,
+            None => return
+        };
+        let mut size = self.cached_size();
+        if size <= budget {
+            return;
+        }
+        let mut candidates = self.asset_data_reference.iter()
+            .filter(|(_, d)| {Arc::strong_count(d) == 1})
+            .map(|(p, d)| {(self.asset_last_used.get(p).cloned().unwrap_or(0), p.clone(), d.data.len())})
+            .collect::<Vec<(u64, String, usize)>>();
+        candidates.sort();
+        for (_, path, len) in candidates {
+            if size <= budget {
+                break;
+            }
+            self.asset_data_reference.remove(&path);
+            self.asset_last_used.remove(&path);
+            size -= len;
+        }
+    }
 
-            asset
+    // finds the asset in the mounted asset packs and folders
+    fn locate_asset(&self, path: &String) -> Result<(PathRep, AssetSource), AssetLoadError> {
+        // first lets get the asset pack that we need
+        // This will need to be the first directory in the path
+        // e.g. ASSET:pack/...
+        let (pack_name, path_list) = AssetManager::split_asset_path(path)?;
+
+        // the latest mount with the file wins
+        let mut known = false;
+        for mount in self.mounts.iter().rev().filter(|m| {m.get_name() == pack_name}) {
+            known = true;
+            if let Some(rep) = mount.rep().find(&path_list).filter(|r| {r.is_file()}) {
+                return Ok((rep.clone(), mount.source(rep)));
+            }
+        }
+        if known {
+            Err(AssetLoadError::UnknownPath(path.clone()))
+        }
+        else {
+            Err(AssetLoadError::UnknownPack(pack_name.to_string()))
+        }
+    }
 
+    // reads the data of a located asset
+    fn read_asset_data(path: &String, rep: &PathRep, source: &AssetSource) -> Result<AssetData, AssetLoadError> {
+        let asset_type = rep.meta_data.get("type")
+            .ok_or(AssetLoadError::MetadataMismatch(path.clone(), "the asset has no type".to_string()))?;
+
+        let mut data = AssetBytes::Owned(vec![]);
+        if asset_type != "Shader" {// we don't want to read shaders from here!! They will be pre loaded by the render pipeline system
+            data = match source {
+                AssetSource::Pack(map, asset_location) => AssetPack::read_mapped(map, asset_location, rep)?,
+                // files in folders are read in rather than mapped, since they can be saved over while we are using them
+                AssetSource::File(file_path) => AssetBytes::Owned(std::fs::read(file_path).map_err(|e| {AssetLoadError::Io(path.clone(), e)})?)
+            };
         }
 
+        Ok(AssetData {
+            asset_name: rep.name.clone(),
+            asset_path: path.clone(),
+            data: data,
+            metadata: rep.meta_data.clone()
+        })
     }
 }
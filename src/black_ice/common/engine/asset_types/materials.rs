@@ -8,7 +8,7 @@ use shaderc::ShaderKind;
 use crate::black_ice::common::{filesystem::files::*, engine::gamesys::*, *};
 use std::sync::Arc;
 
-use super::{super::super::engine::pipeline::RenderPipelineSystem, shader_asset::*, AssetResource};
+use super::{super::super::engine::pipeline::RenderPipelineSystem, shader_asset::*, texture::Texture, AssetResource};
 
 
 // pub struct ParamDescriptor {
@@ -38,6 +38,7 @@ pub struct Material {
     
     pub shader: Shader,
     pub shader_descriptor: HashMap<String, (Arc<Mutex<ShaderDataType>>, ShaderDataHint)>,
+    pub textures: HashMap<String, Arc<Mutex<Texture>>>,// the textures for each sampler in the shader
 
 }
 
@@ -53,6 +54,8 @@ impl Clone for Material {
             
             mat.shader_descriptor.insert(param.to_string(), (Arc::new(Mutex::new(data_type.clone())), value.1.clone()));
         }
+        // textures are shared, there's no need to load them again
+        mat.textures = self.textures.clone();
         return mat;
     }
 }
@@ -62,7 +65,19 @@ impl Base for Material{}
 impl New<Material> for Material {
     fn new() -> Material {
         let mut shader: Shader = AssetManager::load_asset("ASSET:assets/shaders/slim-shadey.shad".to_string());
-        return Material {shader: shader, shader_descriptor: HashMap::new() };
+        return Material {shader: shader, shader_descriptor: HashMap::new(), textures: HashMap::new() };
+    }
+}
+
+impl Material {
+
+    // sets a uniform that gets passed to the shader
+    pub fn set_param(&mut self, name: String, value: ShaderDataType) {
+        self.shader_descriptor.insert(name, (Arc::new(Mutex::new(value)), ShaderDataHint::Uniform));
+    }
+
+    pub fn set_texture(&mut self, name: String, texture: Texture) {
+        self.textures.insert(name, Arc::new(Mutex::new(texture)));
     }
 }
 
@@ -0,0 +1,62 @@
+use std::{collections::HashMap, sync::Arc};
+use parking_lot::Mutex;
+
+use crate::black_ice::common::engine::asset_mgr::{AssetData, AssetLoadError, AssetManager};
+use crate::black_ice::common::filesystem::files::MFType;
+use crate::black_ice::common::mesh::{Mesh, MeshFile, MeshInstanciate};
+
+use super::{materials::Material, AssetResource};
+
+/** A mesh file that is loaded through the asset manager, so that meshes can live in asset packs like everything else:
+*     let mesh: MeshAsset = AssetManager::load_asset("ASSET:assets/models/crate.gltf".to_string());
+* Anything the mesh refers to (mtllibs, glTF buffers and textures) is loaded from next to it in the same mount.
+*/
+pub struct MeshAsset {
+    mesh_file: Option<MeshFile>,
+    pub asset_path: String,
+}
+
+impl MeshAsset {
+
+    pub fn get_mesh_file(&self) -> Option<&MeshFile> {
+        self.mesh_file.as_ref()
+    }
+
+    pub fn into_mesh_file(self) -> Option<MeshFile> {
+        self.mesh_file
+    }
+
+    pub fn as_mesh(&self) -> Mesh {
+        self.mesh_file.as_ref().expect("Mesh asset hasn't been loaded!!").as_mesh()
+    }
+
+    pub fn get_materials(&self) -> HashMap<u32, Arc<Mutex<Material>>> {
+        self.mesh_file.as_ref().map(|m| {m.materials.clone()}).unwrap_or_default()
+    }
+}
+
+impl AssetResource for MeshAsset {
+    fn new() -> Self {
+        MeshAsset { mesh_file: None, asset_path: "".to_string() }
+    }
+
+    fn init(&mut self, data: Arc<AssetData>) -> Result<(), AssetLoadError> {
+        if data.metadata.get("type").map(|t| {t.as_str()}) != Some("Mesh") {
+            return Err(AssetLoadError::MetadataMismatch(data.asset_path.clone(), "it is not a mesh type".to_string()));
+        }
+        let ext = data.metadata.get("ext").cloned().unwrap_or_default().to_lowercase();
+        let mut mesh_file = MeshFile::new();
+        mesh_file.import(data.asset_path.clone(), MFType::from_ext(ext.as_str()), data.data.as_slice(), &|path: &str| {
+            // the mesh needs reloading if any of the files it uses changes
+            AssetManager::add_dependency(data.asset_path.clone(), path.to_string());
+            AssetManager::get_asset_data(path.to_string()).ok().map(|d| {d.data.to_vec()})
+        });
+        self.mesh_file = Some(mesh_file);
+        self.asset_path = data.asset_path.clone();
+        Ok(())
+    }
+
+    fn unload(&mut self) {
+        self.mesh_file = None;
+    }
+}
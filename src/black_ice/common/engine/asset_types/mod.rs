@@ -1,7 +1,7 @@
 
 use std::sync::Arc;
 use std::{fmt, error::Error};
-use crate::black_ice::common::engine::asset_mgr::AssetData;
+use crate::black_ice::common::engine::asset_mgr::{AssetData, AssetLoadError};
 
 pub mod shader_asset;
 
@@ -38,8 +38,8 @@ pub trait AssetResource {
 
     fn new() -> Self;
 
-    fn init(&mut self, data: Arc<AssetData>); // loads the asset's data. This needs to be defined in order for the
-    // asset manager to be able to process your custom asset resource
+    fn init(&mut self, data: Arc<AssetData>) -> Result<(), AssetLoadError>; // loads the asset's data. This needs to be defined in order for the
+    // asset manager to be able to process your custom asset resource. Bad data should give back an error, not panic!!
 
     fn update(&mut self) -> Result<OutputData, AssetResourceUpdateError>{
         return Ok(OutputData::NONE);
@@ -59,3 +59,4 @@ pub trait AssetResource {
 
 pub mod texture;
 pub mod materials;
+pub mod mesh_asset;
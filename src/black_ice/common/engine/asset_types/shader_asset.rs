@@ -5,7 +5,7 @@ use crate::black_ice::common::{Env, Ptr, Base};
 use shaderc::ShaderKind;
 
 
-use super::AssetResource;
+use super::{AssetResource, AssetResourceUpdateError, OutputData};
 
 #[derive(Clone, PartialEq)]
 pub struct StructDescriptor {
@@ -271,6 +271,7 @@ struct ShaderToken {
     pub shader_type: ShaderType,
     pub shader_lang: ShaderLang,
     pub shader_inout_datas: Vec<(String, DataType, ShaderDataHint)>,
+    pub shader_inout_source: Vec<String>,// the inout datas as written in the file, so the cooker can write them back out
     pub shader_code: Vec<u8>,
     pub is_compiled: bool,
 
@@ -308,8 +309,10 @@ impl ShaderData {
             let render_sys = p_render_sys.read();
             for (shader_name, (_asset_path, data)) in &render_sys.registered_shaders {
                 // we will now need to load each shader that can be imported!!
-                
-                context.include(shader_name, &String::from_utf8(data.clone()).unwrap());
+                // cooked shaders are already SPIR-V, so they can't be included
+                if let Ok(text) = String::from_utf8(data.clone()) {
+                    context.include(shader_name.clone(), text);
+                }
             }
         }
 
@@ -328,12 +331,18 @@ impl ShaderData {
         return context;
     }
 
-    pub fn compile(&mut self, shader_type: ShaderType, _shader_lang: ShaderLang, name: String){
+    pub fn compile(&mut self, shader_type: ShaderType, shader_lang: ShaderLang, name: String){
+        let context = ShaderData::include_shaders();
+        self.compile_with(shader_type, shader_lang, name, &context);
+    }
+
+    // compiles using the given includes instead of the ones registered with the render system.
+    // The asset cooker uses this, since it runs before the engine has been started!!
+    pub fn compile_with(&mut self, shader_type: ShaderType, _shader_lang: ShaderLang, name: String, context: &glsl_include::Context){
         let compiler = shaderc::Compiler::new().expect("Failed to init shaderc!!");
         let data_ptr = self.data.clone();
         let data = data_ptr.lock();
         let text = std::str::from_utf8(&data).expect("Data is not of proper UTF8 form!!");
-        let context = ShaderData::include_shaders();
         let temp_text = context.expand(text).expect("Failed to include neseccary shaders!!");
 
         let mut options = shaderc::CompileOptions::new().expect("Failed to create shader options!!");
@@ -362,7 +371,13 @@ impl ShaderData {
         //     let _type = uniform.descriptor_type;
 
         // }
-        let module = spirv_cross::spirv::Module::from_words(temp.as_binary());
+        self.reflect(temp.as_binary());
+        self.compiled_data = Some(Arc::new(Mutex::new(temp.as_binary_u8().to_vec())));
+    }
+
+    // reads the uniforms out of the compiled SPIR-V
+    pub fn reflect(&mut self, words: &[u32]) {
+        let module = spirv_cross::spirv::Module::from_words(words);
         let ast = spirv_cross::spirv::Ast::<spirv_cross::glsl::Target>::parse(&module).unwrap();
         let resources = ast.get_shader_resources().unwrap();
         for uniform in resources.uniform_buffers {
@@ -391,8 +406,6 @@ impl ShaderData {
             }
 
         }
-        
-        self.compiled_data = Some(Arc::new(Mutex::new(temp.as_binary_u8().to_vec())));
     }
 
     pub fn infer_shader_type(&mut self) -> ShaderType {
@@ -595,6 +608,7 @@ impl Shader {
                             _ => false,
                         };
                         let mut inout_datas: Vec<(String, DataType, ShaderDataHint)> = vec![];
+                        let mut inout_source: Vec<String> = vec![];
                         if data_list.contains_key("shader_inout_datas") {
                             let temp = data_list.get("shader_inout_datas").expect("No such value shader_inout_datas!!").clone().as_vec();
                             for val in temp 
@@ -603,6 +617,7 @@ impl Shader {
                                     let string = String::from_utf8(val).expect("Failed to parse string!");
                                 
                                     let temp_list: Vec<&str> = string.split(';').collect();
+                                    inout_source.push(string.clone());
                                     
                                     let name = temp_list[0].to_string();
                                     let data_type = match temp_list[1] {
@@ -646,6 +661,7 @@ impl Shader {
                             shader_name: _shader_name, 
                             shader_type: _shader_type, 
                             shader_inout_datas: inout_datas, 
+                            shader_inout_source: inout_source,
                             shader_code: data_list.get("shader_code").unwrap_or_else(|| {&default_code}).clone().as_str().clone(),
                             shader_lang: _shader_lang,
                             is_compiled: _is_compiled
@@ -754,6 +770,230 @@ impl Shader {
 
         return tokens;
     }
+
+    // compiles all of the stages of a shader, giving back the name of the shader and its stages
+    fn compile_stages(data: &asset_mgr::AssetData, shader_data: Vec<u8>) -> (String, Vec<ShaderStage>) {
+        let mut stages: Vec<ShaderStage> = vec![];
+        let ext = data.metadata["ext"].clone();
+        let path = PathBuf::from(data.asset_path.clone());
+        let file_stem = String::from(path.file_name().unwrap().to_str().unwrap());
+        let file_name = String::from(&file_stem[..file_stem.find(".").unwrap()]);
+        unsafe {
+            match ext.as_str() {
+                "shad" => {
+                    // parse file and load shader stages
+                    let mut tokens = Self::parse_shad_file(&shader_data);
+                    for token in tokens {
+                        let mut stage_ext = ".shad";
+                        match token.shader_lang {
+                            ShaderLang::Glsl => {
+                                match token.shader_type {
+                                    ShaderType::Compute => stage_ext = ".comp",
+                                    ShaderType::Fragment => stage_ext = ".frag",
+                                    ShaderType::Vertex => stage_ext = ".vert",
+                                    ShaderType::Infer => stage_ext = ".glsl"
+                                }
+                            },
+                            ShaderLang::Hlsl => stage_ext = ".hlsl",
+                            ShaderLang::GodotShader => stage_ext = ".gdshad",
+                            ShaderLang::Pssl => stage_ext = ".pfx",
+                        }
+                        if !token.is_compiled {
+                            let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(token.shader_code.clone())), compiled_data: None , descriptor: ShaderStageDescriptor::default()};
+                            shader_data.compile(token.shader_type.clone(), token.shader_lang.clone(), file_name.clone() + stage_ext);
+                            stages.push(ShaderStage::new(file_name.clone() + stage_ext, token.shader_type, token.shader_lang.clone(), shader_data, token.shader_inout_datas));
+                        }
+                        else {
+                            let code = token.shader_code.clone();
+                            let code_u32 = code.chunks_exact(4).map(|w| {u32::from_le_bytes([w[0], w[1], w[2], w[3]])}).collect::<Vec<u32>>();
+                            let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(token.shader_code.clone())), compiled_data: Some(Arc::new(Mutex::new(code.to_vec()))), descriptor: ShaderStageDescriptor::default() };
+                            shader_data.reflect(&code_u32);
+                            stages.push(ShaderStage::new(file_name.clone() + stage_ext, token.shader_type, token.shader_lang.clone(), shader_data, token.shader_inout_datas));
+                        }
+                    }
+                },
+                "vert" => {
+                    //parse as single stage
+                    let mut vec = shader_data.clone();
+                    let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec)), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
+                    shader_data.compile(ShaderType::Vertex, ShaderLang::Glsl, file_stem.clone());
+                    stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Vertex, ShaderLang::Glsl, shader_data, vec![]));
+                },
+                "frag" => {
+                    //parse as single stage
+                    let mut vec = &shader_data;
+                    let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
+                    shader_data.compile(ShaderType::Fragment, ShaderLang::Glsl, file_stem.clone());
+                    stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Fragment, ShaderLang::Glsl, shader_data, vec![]));
+                },
+                "glsl" => {
+                    //parse as single stage
+                    let mut vec = &shader_data;
+                    let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
+                    shader_data.compile(ShaderType::Infer, ShaderLang::Glsl, file_stem.clone());
+                    stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Infer, ShaderLang::Glsl, shader_data, vec![]));
+                },
+                "comp" => {
+                    //parse as single stage
+                    let mut vec = &shader_data;
+                    let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
+                    shader_data.compile(ShaderType::Compute, ShaderLang::Glsl, file_stem.clone());
+                    stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Compute, ShaderLang::Glsl, shader_data, vec![]));
+                },
+                "hlsl" => {
+                    //parse as single stage
+                    let mut vec = &shader_data;
+                    let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
+                    shader_data.hlsl_compile(file_stem.clone());
+                    stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Infer, ShaderLang::Hlsl, shader_data, vec![]));
+                },
+                "fx" => {
+                    //parse as single stage
+                    let mut vec = &shader_data;
+                    let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
+                    shader_data.hlsl_compile(file_stem.clone());
+                    stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Infer, ShaderLang::Hlsl, shader_data, vec![]));
+                },
+                "pfx" => {
+                    //parse as single stage
+                    let mut vec = &shader_data;
+                    let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
+                    stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Infer, ShaderLang::Pssl, shader_data, vec![]));
+                    panic!("Unimplemented!");
+                },
+                "gdshad" => {
+                    //parse as single stage
+                    let mut vec = &shader_data;
+                    let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
+                    stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Infer, ShaderLang::GodotShader, shader_data, vec![]));
+                    panic!("Unimplemented!!");
+                }
+                _ => {
+                    panic!("File is not a shader type!!");
+                }
+            }
+        }
+        (file_name, stages)
+    }
+
+    // gets the names of the shaders included with #include "name" or #include <name>
+    fn find_includes(shader_data: &Vec<u8>) -> Vec<String> {
+        String::from_utf8_lossy(shader_data).lines()
+            .filter_map(|line| {line.trim().strip_prefix("#include")})
+            .filter_map(|rest| {
+                let rest = rest.trim();
+                let end = match rest.chars().next() {
+                    Some('"') => '"',
+                    Some('<') => '>',
+                    _ => return None
+                };
+                rest[1..].find(end).map(|i| {rest[1..i + 1].to_string()})
+            })
+            .collect()
+    }
+
+    // gets the source of a registered shader, which is pre loaded by the asset manager
+    fn registered_data(asset_name: &String) -> Vec<u8> {
+        unsafe {
+            let p_render_sys = Env::get_render_sys();
+            let render_sys = p_render_sys.read();
+            render_sys.registered_shaders[asset_name].1.clone()
+        }
+    }
+
+    /** Compiles a shader file down to a .shad file holding SPIR-V for each stage, marked with is_compiled: true, so
+    * that it doesn't need to be compiled again when it is loaded. The includes are taken from the given context as the
+    * render system won't be running when we cook!!
+    * Gives back None for shaders that can't be cooked (.glsl files that are included by other shaders, and hlsl
+    * which we can't tell the stages of yet), these should be kept as they are.
+    */
+    pub fn cook(data: &[u8], ext: &str, file_name: &String, context: &glsl_include::Context) -> Option<Vec<u8>> {
+        let tokens = match ext {
+            "shad" => Self::parse_shad_file(&data.to_vec()),
+            "vert" | "frag" | "comp" => {
+                let shader_type = match ext {
+                    "vert" => ShaderType::Vertex,
+                    "frag" => ShaderType::Fragment,
+                    _ => ShaderType::Compute,
+                };
+                vec![ShaderToken { 
+                    shader_name: file_name.clone(), 
+                    shader_type, 
+                    shader_lang: ShaderLang::Glsl, 
+                    shader_inout_datas: vec![], 
+                    shader_inout_source: vec![], 
+                    shader_code: data.to_vec(), 
+                    is_compiled: false 
+                }]
+            },
+            _ => return None
+        };
+
+        let mut out: Vec<u8> = vec![];
+        for token in tokens {
+            let code = if token.is_compiled {
+                token.shader_code.clone()
+            }
+            else {
+                let stage_ext = match token.shader_type {
+                    ShaderType::Compute => ".comp",
+                    ShaderType::Fragment => ".frag",
+                    ShaderType::Vertex => ".vert",
+                    ShaderType::Infer => ".glsl"
+                };
+                let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(token.shader_code.clone())), compiled_data: None , descriptor: ShaderStageDescriptor::default()};
+                shader_data.compile_with(token.shader_type.clone(), token.shader_lang.clone(), file_name.clone() + stage_ext, context);
+                let compiled = shader_data.compiled_data.expect("Shader was not compiled!!");
+                let code = compiled.lock().clone();
+                code
+            };
+            Self::write_shad_token(&mut out, &token, &code);
+        }
+        Some(out)
+    }
+
+    // writes a single stage in the same layout that parse_shad_file reads
+    fn write_shad_token(out: &mut Vec<u8>, token: &ShaderToken, code: &[u8]) {
+        // everything is quoted, so we only need to escape quotes and backslashes!!
+        fn quoted(out: &mut Vec<u8>, data: &[u8]) {
+            out.push(b'"');
+            for c in data {
+                if *c == b'"' || *c == b'\\' {
+                    out.push(b'\\');
+                }
+                out.push(*c);
+            }
+            out.push(b'"');
+        }
+
+        let shader_type: &[u8] = match token.shader_type {
+            ShaderType::Fragment => b"fragment",
+            ShaderType::Vertex => b"vertex",
+            _ => b"compute",
+        };
+        let shader_lang: &[u8] = match token.shader_lang {
+            ShaderLang::Glsl => b"glsl",
+            ShaderLang::Hlsl => b"hlsl",
+            ShaderLang::Pssl => b"pgsl",
+            ShaderLang::GodotShader => b"godot",
+        };
+
+        out.extend_from_slice(b"{\n    \"shader_name\": ");
+        quoted(out, token.shader_name.as_bytes());
+        out.extend_from_slice(b",\n    \"shader_type\": ");
+        quoted(out, shader_type);
+        out.extend_from_slice(b",\n    \"shader_lang\": ");
+        quoted(out, shader_lang);
+        out.extend_from_slice(b",\n    \"is_compiled\": \"true\",\n    \"shader_inout_datas\": [");
+        // every value needs a comma after it, even the last one in a list!!
+        for inout in &token.shader_inout_source {
+            quoted(out, inout.as_bytes());
+            out.push(b',');
+        }
+        out.extend_from_slice(b"],\n    \"shader_code\": ");
+        quoted(out, code);
+        out.extend_from_slice(b",\n}\n");
+    }
 }
 
 
@@ -763,121 +1003,27 @@ impl AssetResource for Shader {
 
     }
 
-    fn init(&mut self, data: std::sync::Arc<asset_mgr::AssetData>) {
+    fn init(&mut self, data: std::sync::Arc<asset_mgr::AssetData>) -> Result<(), asset_mgr::AssetLoadError> {
 
         // we should check if the shader has already been registered!!
 
-        if data.metadata["type"] != "Shader"{
-            panic!("File is not a shader type!!");
+        if data.metadata.get("type").map(|t| {t.as_str()}) != Some("Shader") {
+            return Err(asset_mgr::AssetLoadError::MetadataMismatch(data.asset_path.clone(), "it is not a shader type".to_string()));
         }
         else{
             // parse data and organise
-            let mut shader_data = Vec::<u8>::new();
-            unsafe {
-            let p_render_sys = Env::get_render_sys();
-            let mut render_sys = p_render_sys.write();
-            let (a, b) = render_sys.registered_shaders[&data.asset_name].clone();
-                shader_data = b;
-            }
-            let mut stages: Vec<ShaderStage> = vec![];
-            let ext = data.metadata["ext"].clone();
-            let path = PathBuf::from(data.asset_path.clone());
-            let file_stem = String::from(path.file_name().unwrap().to_str().unwrap());
-            let file_name = String::from(&file_stem[..file_stem.find(".").unwrap()]);
-            unsafe {
-                match ext.as_str() {
-                    "shad" => {
-                        // parse file and load shader stages
-                        let mut tokens = Self::parse_shad_file(&shader_data);
-                        for token in tokens {
-                            let mut stage_ext = ".shad";
-                            match token.shader_lang {
-                                ShaderLang::Glsl => {
-                                    match token.shader_type {
-                                        ShaderType::Compute => stage_ext = ".comp",
-                                        ShaderType::Fragment => stage_ext = ".frag",
-                                        ShaderType::Vertex => stage_ext = ".vert",
-                                        ShaderType::Infer => stage_ext = ".glsl"
-                                    }
-                                },
-                                ShaderLang::Hlsl => stage_ext = ".hlsl",
-                                ShaderLang::GodotShader => stage_ext = ".gdshad",
-                                ShaderLang::Pssl => stage_ext = ".pfx",
-                            }
-                            if !token.is_compiled {
-                                let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(token.shader_code.clone())), compiled_data: None , descriptor: ShaderStageDescriptor::default()};
-                                shader_data.compile(token.shader_type.clone(), token.shader_lang.clone(), file_name.clone() + stage_ext);
-                                stages.push(ShaderStage::new(file_name.clone() + stage_ext, token.shader_type, token.shader_lang.clone(), shader_data, token.shader_inout_datas));
-                            }
-                            else {
-                                let code = token.shader_code.clone();
-                                let _code_u32 = code.align_to::<u32>().1;
-                                let shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(token.shader_code.clone())), compiled_data: Some(Arc::new(Mutex::new(code.to_vec()))), descriptor: ShaderStageDescriptor::default() };
-                                stages.push(ShaderStage::new(file_name.clone() + stage_ext, token.shader_type, token.shader_lang.clone(), shader_data, token.shader_inout_datas));
-                            }
-                        }
-                    },
-                    "vert" => {
-                        //parse as single stage
-                        let mut vec = shader_data.clone();
-                        let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec)), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
-                        shader_data.compile(ShaderType::Vertex, ShaderLang::Glsl, file_stem.clone());
-                        stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Vertex, ShaderLang::Glsl, shader_data, vec![]));
-                    },
-                    "frag" => {
-                        //parse as single stage
-                        let mut vec = &shader_data;
-                        let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
-                        shader_data.compile(ShaderType::Fragment, ShaderLang::Glsl, file_stem.clone());
-                        stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Fragment, ShaderLang::Glsl, shader_data, vec![]));
-                    },
-                    "glsl" => {
-                        //parse as single stage
-                        let mut vec = &shader_data;
-                        let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
-                        shader_data.compile(ShaderType::Infer, ShaderLang::Glsl, file_stem.clone());
-                        stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Infer, ShaderLang::Glsl, shader_data, vec![]));
-                    },
-                    "comp" => {
-                        //parse as single stage
-                        let mut vec = &shader_data;
-                        let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
-                        shader_data.compile(ShaderType::Compute, ShaderLang::Glsl, file_stem.clone());
-                        stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Compute, ShaderLang::Glsl, shader_data, vec![]));
-                    },
-                    "hlsl" => {
-                        //parse as single stage
-                        let mut vec = &shader_data;
-                        let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
-                        shader_data.hlsl_compile(file_stem.clone());
-                        stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Infer, ShaderLang::Hlsl, shader_data, vec![]));
-                    },
-                    "fx" => {
-                        //parse as single stage
-                        let mut vec = &shader_data;
-                        let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
-                        shader_data.hlsl_compile(file_stem.clone());
-                        stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Infer, ShaderLang::Hlsl, shader_data, vec![]));
-                    },
-                    "pfx" => {
-                        //parse as single stage
-                        let mut vec = &shader_data;
-                        let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
-                        stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Infer, ShaderLang::Pssl, shader_data, vec![]));
-                        panic!("Unimplemented!");
-                    },
-                    "gdshad" => {
-                        //parse as single stage
-                        let mut vec = &shader_data;
-                        let mut shader_data: ShaderData = ShaderData { data: Arc::new(Mutex::new(vec.clone())), compiled_data: None, descriptor: ShaderStageDescriptor::default() };
-                        stages.push(ShaderStage::new(file_stem.clone(), ShaderType::Infer, ShaderLang::GodotShader, shader_data, vec![]));
-                        panic!("Unimplemented!!");
-                    }
-                    _ => {
-                        panic!("File is not a shader type!!");
+            let shader_data = Self::registered_data(&data.asset_name);
+            // includes are pulled in by glsl_include rather than the asset manager, so we need to record them ourselves
+            for include in Self::find_includes(&shader_data) {
+                unsafe {
+                    let p_render_sys = Env::get_render_sys();
+                    let render_sys = p_render_sys.read();
+                    if let Some((include_path, _)) = render_sys.registered_shaders.get(&include) {
+                        AssetManager::add_dependency(data.asset_path.clone(), include_path.clone());
                     }
                 }
             }
+            let (file_name, stages) = Self::compile_stages(&data, shader_data);
             let mut stages_ptr = Vec::<ShaderPtr>::new();
 
             for stage in stages {
@@ -889,10 +1035,47 @@ impl AssetResource for Shader {
             self.shader_name = file_name.clone();
             self.shader_stages = stages_ptr;
             self.asset_path = data.asset_path.clone();
+
+            // the stages are shared by name, so recompiling them once updates every shader loaded from this asset
+            let asset_path = data.asset_path.clone();
+            AssetManager::set_reload_hook(data.asset_path.clone(), Box::new(move || {
+                let mut shader = Shader::new();
+                shader.asset_path = asset_path.clone();
+                if shader.update().is_err() {
+                    println!("Failed to reload shader {}!!", asset_path);
+                }
+                true
+            }));
         }
+        Ok(())
+    }
+
+    fn update(&mut self) -> Result<OutputData, AssetResourceUpdateError> {
+        // the asset manager re-registers the shader source when it changes on disk, so we just need to recompile it
+        let data = AssetManager::get_asset_data(self.asset_path.clone()).map_err(|_| {AssetResourceUpdateError {}})?;
+        let shader_data = Self::registered_data(&data.asset_name);
+        let (_, stages) = Self::compile_stages(&data, shader_data);
+        let mut stages_ptr = Vec::<ShaderPtr>::new();
+        for stage in stages {
+            unsafe {
+                stages_ptr.push(RenderPipelineSystem::update_shader_stage(stage));
+            }
+        }
+        self.shader_stages = stages_ptr;
+        unsafe {
+            RenderPipelineSystem::invalidate_shader_program(self.asset_path.clone());
+        }
+        Ok(OutputData::NONE)
     }
 
     fn unload(&mut self) {
-        todo!()
+        // the stages stay registered since other shaders can share them (they are looked up by name), but the programs
+        // made from this shader are thrown away
+        self.shader_stages.clear();
+        if !self.asset_path.is_empty() {
+            unsafe {
+                RenderPipelineSystem::invalidate_shader_program(self.asset_path.clone());
+            }
+        }
     }
 }
\ No newline at end of file
@@ -1,7 +1,7 @@
 
-use crate::{black_ice::common::engine::{asset_mgr::*, asset_types::*, pipeline::Image}, Env};
+use crate::{black_ice::common::engine::{asset_mgr::*, asset_types::*, mipmap::{self, MipFilter}, pipeline::{AtlasRegion, BlockCompression, Image, SamplerSettings, TextureFormat}}, Env};
 use core::{error, panic};
-use std::{collections::HashMap, io::{BufRead, BufReader, Cursor, Error, Read, SeekFrom}, string::ParseError, sync::Arc};
+use std::{collections::HashMap, io::{BufRead, BufReader, Cursor, Error, ErrorKind, Read, SeekFrom}, string::ParseError, sync::Arc};
 use image::GenericImageView;
 use parking_lot::Mutex;
 use sdl2::render;
@@ -13,19 +13,227 @@ pub struct Texture {
     // that may be updated during runtime
 
     pub asset_path: String,
+    region: Option<AtlasRegion>,// where we are in image_data, if it is an atlas
 
 }
 
+// The parts of a DDS header that we need to upload the blocks
+pub struct DdsHeader {
+    pub width: u32,
+    pub height: u32,
+    pub mip_count: u32,
+    pub format: BlockCompression,
+    pub srgb: bool,
+    pub data_offset: usize,// where the blocks start in the file
+}
+
+// the magic, the header and the DX10 header. Reading this much of a DDS file is enough to get the header!!
+pub const DDS_HEADER_SIZE: usize = 148;
+
 impl Texture {
 
-    pub fn parse_png(data: &Vec<u8>) -> Result<(Vec<[u32; 4]>, u32, u32), Error> {
+    // uses the region of an atlas that the image at asset_path was packed into, instead of loading the image by itself.
+    // Gives back None if the image isn't in the atlas!!
+    pub fn from_atlas(atlas: Arc<Mutex<Image>>, asset_path: String) -> Option<Self> {
+        let region = atlas.lock().get_region(&asset_path)?;
+        Some(Texture { image_data: Some(atlas), asset_path, region: Some(region) })
+    }
+
+    pub fn get_image(&self) -> Option<Arc<Mutex<Image>>> {
+        self.image_data.clone()
+    }
+
+    // the part of the image that this texture uses, which is all of it unless it is in an atlas
+    pub fn get_region(&self) -> AtlasRegion {
+        match (self.region, self.image_data.as_ref()) {
+            (Some(region), _) => region,
+            (None, Some(image)) => {
+                let image = image.lock();
+                AtlasRegion::full(image.get_width(), image.get_height())
+            },
+            (None, None) => AtlasRegion::full(0, 0)
+        }
+    }
+
+    // makes a texture out of an image that isn't its own asset (like an image inside a mesh file). The name is what it
+    // gets registered with the render system as, so the same image is only ever decoded once
+    pub fn from_memory(name: String, data: &[u8], metadata: &HashMap<String, String>) -> Result<Self, Error> {
+        unsafe {
+            let p_render_sys = Env::get_render_sys();
+            let found = p_render_sys.read().find_image(name.clone());
+            let image = match found {
+                Ok(image) => image,
+                // decoding takes a while, so the render system isn't locked until there is something to insert.
+                // If another thread beat us to it, insert_image gives back the image that it registered
+                Err(_) => {
+                    let decoded = Texture::decode(data, metadata)?;
+                    p_render_sys.write().insert_image(decoded, name.clone())
+                }
+            };
+            Ok(Texture { image_data: Some(image), asset_path: name, region: None })
+        }
+    }
+
+    // cooked data is always little endian, but images are kept in the byte order of the machine so that they can be
+    // uploaded as they are. Swapping is the same both ways, so this is used for writing and reading cooked data
+    pub fn swap_le_bytes(data: &mut [u8], format: TextureFormat) {
+        let size = format.bytes_per_channel();
+        if cfg!(target_endian = "big") && size > 1 {
+            for channel in data.chunks_exact_mut(size) {
+                channel.reverse();
+            }
+        }
+    }
+
+    // these are kept block compressed instead of being decoded
+    pub fn is_block_compressed(ext: &str) -> bool {
+        ext == "dds" || ext == "dxt"
+    }
+
+    // reads the header of a block compressed DDS file (BC1 - BC7). Only the first DDS_HEADER_SIZE bytes are needed.
+    // Uncompressed DDS files aren't supported!!
+    pub fn parse_dds(data: &[u8]) -> Result<DdsHeader, Error> {
+        let read = |offset: usize| -> Result<u32, Error> {
+            data.get(offset..offset + 4)
+                .map(|b| {u32::from_le_bytes([b[0], b[1], b[2], b[3]])})
+                .ok_or(Error::new(ErrorKind::UnexpectedEof, "DDS file is too short!!"))
+        };
+        if data.len() < 128 || &data[0..4] != b"DDS " {
+            return Err(Error::new(ErrorKind::InvalidData, "This is not a DDS file!!"));
+        }
+        let height = read(12)?;
+        let width = read(16)?;
+        // the mip count is only set if the file says it is
+        let mip_count = if read(8)? & 0x20000 != 0 { read(28)?.max(1) } else { 1 };
+        if read(80)? & 0x4 == 0 {
+            return Err(Error::new(ErrorKind::Unsupported, "Only block compressed DDS files can be loaded!!"));
+        }
+        let (format, srgb, data_offset) = match &data[84..88] {
+            b"DXT1" => (BlockCompression::BC1, false, 128),
+            b"DXT2" | b"DXT3" => (BlockCompression::BC2, false, 128),
+            b"DXT4" | b"DXT5" => (BlockCompression::BC3, false, 128),
+            b"ATI1" | b"BC4U" => (BlockCompression::BC4, false, 128),
+            b"BC4S" => (BlockCompression::BC4S, false, 128),
+            b"ATI2" | b"BC5U" => (BlockCompression::BC5, false, 128),
+            b"BC5S" => (BlockCompression::BC5S, false, 128),
+            b"DX10" => {
+                // the format is a DXGI_FORMAT in the extra header
+                let dxgi_format = read(128)?;
+                let format = match dxgi_format {
+                    70 | 71 | 72 => BlockCompression::BC1,
+                    73 | 74 | 75 => BlockCompression::BC2,
+                    76 | 77 | 78 => BlockCompression::BC3,
+                    79 | 80 => BlockCompression::BC4,
+                    81 => BlockCompression::BC4S,
+                    82 | 83 => BlockCompression::BC5,
+                    84 => BlockCompression::BC5S,
+                    94 | 95 => BlockCompression::BC6H,
+                    96 => BlockCompression::BC6HS,
+                    97 | 98 | 99 => BlockCompression::BC7,
+                    _ => return Err(Error::new(ErrorKind::Unsupported, format!("DXGI format {} is not a block compressed format!!", dxgi_format)))
+                };
+                (format, matches!(dxgi_format, 72 | 75 | 78 | 99), DDS_HEADER_SIZE)
+            },
+            four_cc => return Err(Error::new(ErrorKind::Unsupported, format!("Unknown DDS format {}!!", String::from_utf8_lossy(four_cc))))
+        };
+        Ok(DdsHeader { width, height, mip_count, format, srgb, data_offset })
+    }
+
+    // keeps the image in the format it was stored in, only converting the formats that can't be uploaded as they are.
+    // 16 bit channels are stored in the byte order of the machine, which is what the GPU wants
+    fn from_dynamic(decoded: image::DynamicImage) -> Image {
+        let (width, height) = (decoded.width(), decoded.height());
+        let (data, format) = match decoded {
+            image::DynamicImage::ImageLuma8(i) => (i.into_raw(), TextureFormat::R8),
+            image::DynamicImage::ImageLumaA8(i) => (i.into_raw(), TextureFormat::RG8),
+            image::DynamicImage::ImageRgb8(i) => (i.into_raw(), TextureFormat::RGB8),
+            image::DynamicImage::ImageRgba8(i) => (i.into_raw(), TextureFormat::RGBA8),
+            image::DynamicImage::ImageLuma16(i) => (i.into_raw().iter().flat_map(|c| {c.to_ne_bytes()}).collect(), TextureFormat::R16),
+            image::DynamicImage::ImageLumaA16(i) => (i.into_raw().iter().flat_map(|c| {c.to_ne_bytes()}).collect(), TextureFormat::RG16),
+            image::DynamicImage::ImageRgb16(_) | image::DynamicImage::ImageRgba16(_) => {
+                (decoded.to_rgba16().into_raw().iter().flat_map(|c| {c.to_ne_bytes()}).collect(), TextureFormat::RGBA16)
+            },
+            image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_) => {
+                (decoded.to_rgba32f().into_raw().iter().flat_map(|c| {c.to_ne_bytes()}).collect(), TextureFormat::RGBA32F)
+            },
+            other => (other.to_rgba8().into_raw(), TextureFormat::RGBA8)
+        };
+        Image::new(data, format, width, height, false)
+    }
+
+    /** Decodes the texture into an Image, making its mip levels and reading its sampler settings from the metadata.
+    * The ext in the metadata is used to work out what the data is:
+    *     raw - cooked data, which is already in the format given by the format, width, height and mips metadata
+    *     dds/dxt - block compressed data, which is kept as it is
+    *     anything else - decoded with the image crate
+    * The srgb metadata can be set to override whether colour textures are sRGB. They are by default, unless they are
+    * DDS files which say that they aren't!! The mipmaps metadata picks the filter that the mips are made with (box, kaiser
+    * or none), which is box by default. The mips and sampler go to the gpu with the image when it is registered.
+    */
+    pub fn decode(data: &[u8], metadata: &HashMap<String, String>) -> Result<Image, Error> {
+        let mut image = Texture::decode_data(data, metadata)?;
+        image.sampler = SamplerSettings::from_meta(metadata);
+        // make the mips unless the image already has them, or they won't be sampled
+        let filter = match metadata.get("mipmaps") {
+            Some(value) => MipFilter::from_meta(value.trim().to_ascii_lowercase().as_str()),
+            None => Some(MipFilter::Box)
+        };
+        if let Some(filter) = filter {
+            if image.get_mip_count() == 1 && image.sampler.mip_filter.is_some() && !image.is_atlas {
+                mipmap::generate_mips(&mut image, filter);
+            }
+        }
+        Ok(image)
+    }
+
+    fn decode_data(data: &[u8], metadata: &HashMap<String, String>) -> Result<Image, Error> {
+        let invalid = |message: &str| {Error::new(ErrorKind::InvalidData, message.to_string())};
+        let ext = metadata.get("ext").ok_or(invalid("Texture has no file extension!!"))?;
+        let srgb = metadata.get("srgb").map(|s| {s == "true"});
+        if ext == "raw" {
+            // cooked textures are already decoded, so we can use the data as it is
+            let format = metadata.get("format").and_then(|f| {TextureFormat::from_str(f)}).ok_or(invalid("Cooked texture has no format!!"))?;
+            let width = metadata.get("width").and_then(|w| {w.parse::<u32>().ok()}).ok_or(invalid("Cooked texture has no width!!"))?;
+            let height = metadata.get("height").and_then(|h| {h.parse::<u32>().ok()}).ok_or(invalid("Cooked texture has no height!!"))?;
+            if data.len() < format.size_of(width, height) {
+                return Err(invalid("Cooked texture is missing its data!!"));
+            }
+            let mut pixels = data.to_vec();
+            Texture::swap_le_bytes(&mut pixels, format);
+            let mut image = Image::new(pixels, format, width, height, false);
+            image.set_mip_count(metadata.get("mips").and_then(|m| {m.parse::<u32>().ok()}).unwrap_or(1));
+            image.set_srgb(srgb.unwrap_or(false));
+            return Ok(image);
+        }
+        if Texture::is_block_compressed(ext) {
+            // the blocks (and all of the mip levels after them) are kept exactly as they are in the file
+            let header = Texture::parse_dds(data)?;
+            let format = TextureFormat::Block(header.format);
+            // make sure that at least the first mip level is there
+            if data.len() < header.data_offset + format.size_of(header.width, header.height) {
+                return Err(invalid("DDS file is missing its data!!"));
+            }
+            let mut image = Image::new(data[header.data_offset..].to_vec(), format, header.width, header.height, false);
+            image.set_mip_count(header.mip_count);
+            image.set_srgb(srgb.unwrap_or(header.srgb));
+            return Ok(image);
+        }
+        let mut mage = image::ImageReader::new(Cursor::new(data));
+        let format = image::ImageFormat::from_extension(ext)
+            .ok_or(Error::new(ErrorKind::Unsupported, format!("Cannot currently load image formats of the type {}", ext)))?;
+        mage.set_format(format);
+        let decoded = mage.decode().map_err(|e| {Error::new(ErrorKind::InvalidData, e)})?;
+        let mut image = Texture::from_dynamic(decoded);
+        image.set_srgb(srgb.unwrap_or(true));
+        Ok(image)
+    }
+
+    pub fn parse_png(data: &Vec<u8>) -> Result<Image, Error> {
 
         let mut mage = image::ImageReader::new(Cursor::new(data.as_slice()));
         mage.set_format(image::ImageFormat::Png);
-        let mut decoded = mage.decode().expect("Failed to read image data. PNG may be corrupted!! Please Report!");
-        let mut buf = decoded.to_rgba16().into_iter().map(|x| {u32::from(x.clone())}).collect::<Vec<u32>>();
-        let buf_2 = buf.chunks_exact(4).map(|chunk| {<[u32; 4]>::try_from(chunk).unwrap()}).collect::<Vec<[u32; 4]>>();
-        let result: (Vec<[u32; 4]>, u32, u32) = (buf_2, decoded.width(), decoded.height());
+        let decoded = mage.decode().map_err(|e| {Error::new(ErrorKind::InvalidData, e)})?;
+        let result = Texture::from_dynamic(decoded);
         // // lets check the first 8 bytes for a valid png
         // if !(data[0..7] == [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']){
         //     // we have a non png file!!
@@ -172,43 +380,73 @@ impl Texture {
 
 impl AssetResource for Texture {
     fn new() -> Self {
-        Texture { image_data:None, asset_path: "".to_string() }
+        Texture { image_data:None, asset_path: "".to_string(), region: None }
     }
 
-    fn init(&mut self, data: std::sync::Arc<AssetData>){
+    fn init(&mut self, data: std::sync::Arc<AssetData>) -> Result<(), AssetLoadError> {
         // the asset data we load in will either be fresh, or already loaded by our render server
         // first we should check with the render server first
         unsafe {
             // this step will be unsafe, so be careful!!
             let p_render_sys = Env::get_render_sys();
-            let mut render_sys = p_render_sys.write();
 
             // lets start processing the image data
             // this will be dropped when we finish loading the image
             let im_data = &data.data;
 
-            if let Ok(image) = render_sys.find_image(data.asset_path.clone()) {
+            let found = p_render_sys.read().find_image(data.asset_path.clone());
+            if let Ok(image) = found {
                 self.image_data = Some(image);
                 self.asset_path = data.asset_path.clone();
-                return;
+                return Ok(());
             }
             else {
-                let mut mage = image::ImageReader::new(Cursor::new(im_data.as_slice()));
-                let format = image::ImageFormat::from_extension(data.metadata.get("ext").unwrap()).expect("Failed to find image format!!");
-                mage.set_format(format);
-                let decoded = mage.decode().expect("Failed to read image data. PNG may be corrupted!! Please Report!");
-                let buf = decoded.to_rgba16().into_iter().map(|x| {u32::from(x.clone())}).collect::<Vec<u32>>();
-                let buf_2 = buf.chunks_exact(4).map(|chunk| {<[u32; 4]>::try_from(chunk).unwrap()}).collect::<Vec<[u32; 4]>>();
-                let image = render_sys.register_image(&buf_2, decoded.width(), decoded.height(), 32, data.asset_path.clone());
+                // the render system is only locked to insert the image, so decoding doesn't hold up the render thread or
+                // other textures loading at the same time
+                let decoded = Texture::decode(im_data.as_slice(), &data.metadata)
+                    .map_err(|e| {AssetLoadError::InvalidData(data.asset_path.clone(), e)})?;
+                let image = p_render_sys.write().insert_image(decoded, data.asset_path.clone());
+                // the image is shared by every texture loaded from this asset, so it gets reloaded once for all of them
+                let weak = Arc::downgrade(&image);
+                let asset_path = data.asset_path.clone();
+                AssetManager::set_reload_hook(data.asset_path.clone(), Box::new(move || {
+                    match weak.upgrade() {
+                        Some(image) => {
+                            let mut texture = Texture { image_data: Some(image), asset_path: asset_path.clone(), region: None };
+                            if texture.update().is_err() {
+                                println!("Failed to reload texture {}!!", asset_path);
+                            }
+                            true
+                        },
+                        None => false
+                    }
+                }));
                 self.image_data = Some(image);
                 self.asset_path = data.asset_path.clone();
             }
 
         }
+        Ok(())
     }
 
     fn update(&mut self) -> Result<OutputData, AssetResourceUpdateError> {
-
+        // the asset manager will have refreshed the data if the image has changed on disk
+        // we replace the registered image in place so that everything sharing it sees the change
+        // a texture in an atlas only has a region of the image, so it can't replace the whole thing!! The atlas has to
+        // be built again to pick up the change
+        if self.region.is_some() {
+            return Ok(OutputData::NONE);
+        }
+        let data = AssetManager::get_asset_data(self.asset_path.clone()).map_err(|_| {AssetResourceUpdateError {}})?;
+        if let Some(image) = self.image_data.as_ref() {
+            let decoded = Texture::decode(data.data.as_slice(), &data.metadata).map_err(|_| {AssetResourceUpdateError {}})?;
+            *image.lock() = decoded;
+            // the sampler and mips may have changed too, so the texture on the gpu has to be made again
+            unsafe {
+                let p_render_sys = Env::get_render_sys();
+                p_render_sys.write().queue_image_upload(self.asset_path.clone());
+            }
+        }
         Ok(OutputData::BYTEARRAY(vec![]))
     }
 
@@ -0,0 +1,255 @@
+use std::{fmt, error::Error, sync::Arc};
+
+use parking_lot::Mutex;
+
+use super::asset_mgr::{AssetLoadError, AssetManager};
+use super::asset_types::texture::Texture;
+use super::mipmap::linear_to_srgb;
+use super::pipeline::{AtlasRegion, Image, TextureFormat};
+use crate::black_ice::common::Env;
+
+#[derive(Debug)]
+pub enum AtlasError {
+    Load(AssetLoadError),
+    BlockCompressed(String),// block compressed images can't be copied into an atlas
+    TooLarge(u32),// the images don't fit in an atlas of the max size
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AtlasError::Load(e) => write!(f, "Failed to load image for atlas!! {}", e),
+            AtlasError::BlockCompressed(name) => write!(f, "Image {} is block compressed and can't be put in an atlas!!", name),
+            AtlasError::TooLarge(max_size) => write!(f, "The images don't fit in a {}x{} atlas!!", max_size, max_size),
+        }
+    }
+}
+
+impl Error for AtlasError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AtlasError::Load(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+/** Packs lots of small images into one RGBA8 image, so that sprites can share a single texture. Each image is looked up
+* in the atlas by the name it was added with (use the asset path for assets), which gives back where it was put:
+*     let mut builder = AtlasBuilder::new(2, 4096);
+*     builder.add_asset("ASSET:assets/images/coin.png".to_string())?;
+*     let atlas = builder.register("ui".to_string())?;
+* The padding around each image is filled with the edge of the image, so that filtering doesn't bleed into its neighbours.
+*/
+pub struct AtlasBuilder {
+    padding: u32,
+    max_size: u32,
+    images: Vec<(String, Image)>,
+}
+
+impl AtlasBuilder {
+
+    pub fn new(padding: u32, max_size: u32) -> Self {
+        Self { padding, max_size, images: vec![] }
+    }
+
+    pub fn add(&mut self, name: String, image: Image) -> Result<(), AtlasError> {
+        if let TextureFormat::Block(_) = image.get_format() {
+            return Err(AtlasError::BlockCompressed(name));
+        }
+        self.images.push((name, image));
+        Ok(())
+    }
+
+    // loads an image asset and adds it with its asset path as its name
+    pub fn add_asset(&mut self, path: String) -> Result<(), AtlasError> {
+        let data = AssetManager::get_asset_data(path.clone()).map_err(AtlasError::Load)?;
+        // only the first level is copied, so don't bother making the mips
+        let mut metadata = data.metadata.clone();
+        metadata.insert("mipmaps".to_string(), "none".to_string());
+        let image = Texture::decode(data.data.as_slice(), &metadata).map_err(|e| {AtlasError::Load(AssetLoadError::InvalidData(path.clone(), e))})?;
+        self.add(path, image)
+    }
+
+    // packs the images, giving back the atlas with a region for each one
+    pub fn build(self) -> Result<Image, AtlasError> {
+        let padding = self.padding;
+        let mut rects: Vec<(usize, u32, u32)> = self.images.iter().enumerate()
+            .map(|(i, (_, image))| {(i, image.get_width() + padding * 2, image.get_height() + padding * 2)})
+            .collect();
+        // tall images first packs a lot tighter
+        rects.sort_by(|a, b| {b.2.cmp(&a.2).then(b.1.cmp(&a.1))});
+
+        // start with the smallest square that could hold everything, and double it until everything fits
+        let area: u64 = rects.iter().map(|(_, w, h)| {*w as u64 * *h as u64}).sum();
+        let largest = rects.iter().map(|(_, w, h)| {*w.max(h)}).max().unwrap_or(1);
+        let mut size = ((area as f64).sqrt().ceil() as u32).max(largest).max(1).next_power_of_two();
+        let placements = loop {
+            if size > self.max_size {
+                return Err(AtlasError::TooLarge(self.max_size));
+            }
+            let mut skyline = Skyline::new(size, size);
+            let placed = rects.iter().map(|(i, w, h)| {skyline.insert(*w, *h).map(|pos| {(*i, pos)})}).collect::<Option<Vec<_>>>();
+            if let Some(placed) = placed {
+                break placed;
+            }
+            size *= 2;
+        };
+
+        let mut data = vec![0u8; size as usize * size as usize * 4];
+        let mut regions: Vec<(String, AtlasRegion)> = vec![];
+        for (i, (x, y)) in placements {
+            let (name, image) = &self.images[i];
+            let (width, height) = (image.get_width(), image.get_height());
+            let pixels = to_rgba8(image);
+            // copy the image, with its edges stretched out into the padding
+            for dy in 0..height + padding * 2 {
+                let sy = (dy as i64 - padding as i64).clamp(0, height as i64 - 1) as usize;
+                for dx in 0..width + padding * 2 {
+                    let sx = (dx as i64 - padding as i64).clamp(0, width as i64 - 1) as usize;
+                    let src = (sy * width as usize + sx) * 4;
+                    let dst = ((y + dy) as usize * size as usize + (x + dx) as usize) * 4;
+                    data[dst..dst + 4].copy_from_slice(&pixels[src..src + 4]);
+                }
+            }
+            let (rx, ry) = (x + padding, y + padding);
+            regions.push((name.clone(), AtlasRegion {
+                x: rx,
+                y: ry,
+                width,
+                height,
+                uv_min: [rx as f32 / size as f32, ry as f32 / size as f32],
+                uv_max: [(rx + width) as f32 / size as f32, (ry + height) as f32 / size as f32]
+            }));
+        }
+
+        let mut atlas = Image::new(data, TextureFormat::RGBA8, size, size, true);
+        atlas.set_srgb(true);
+        atlas.set_max_size(self.max_size, self.max_size);
+        // mips would blend the images together, so only the first level is ever used
+        atlas.sampler.mip_filter = None;
+        for (name, region) in regions {
+            atlas.set_region(name, region);
+        }
+        Ok(atlas)
+    }
+
+    // builds the atlas and registers it with the render system, so that sprites can find it by name
+    pub fn register(self, name: String) -> Result<Arc<Mutex<Image>>, AtlasError> {
+        let atlas = self.build()?;
+        unsafe {
+            let p_render_sys = Env::get_render_sys();
+            let mut render_sys = p_render_sys.write();
+            Ok(render_sys.insert_image(atlas, name))
+        }
+    }
+}
+
+// converts any uncompressed image to sRGB RGBA8, since that's what the atlas is. One and two channel images are grey, the
+// same as when they are uploaded. Images that aren't sRGB (like the 16 bit and float ones) are converted, so that they
+// still look the same once they are sampled out of the atlas
+fn to_rgba8(image: &Image) -> Vec<u8> {
+    let data = image.get_mip(0).expect("Image has no data!!").2;
+    let u8_at = |i: usize| {data[i] as f32 / 255.0};
+    let u16_at = |i: usize| {u16::from_ne_bytes([data[i * 2], data[i * 2 + 1]]) as f32 / 65535.0};
+    let f32_at = |i: usize| {f32::from_ne_bytes([data[i * 4], data[i * 4 + 1], data[i * 4 + 2], data[i * 4 + 3]])};
+    let srgb = image.is_srgb();
+    let pixels = image.get_width() as usize * image.get_height() as usize;
+    (0..pixels).flat_map(|p| {
+        let pixel = match image.get_format() {
+            TextureFormat::R8 => [u8_at(p), u8_at(p), u8_at(p), 1.0],
+            TextureFormat::RG8 => [u8_at(p * 2), u8_at(p * 2), u8_at(p * 2), u8_at(p * 2 + 1)],
+            TextureFormat::RGB8 => [u8_at(p * 3), u8_at(p * 3 + 1), u8_at(p * 3 + 2), 1.0],
+            TextureFormat::RGBA8 => [u8_at(p * 4), u8_at(p * 4 + 1), u8_at(p * 4 + 2), u8_at(p * 4 + 3)],
+            TextureFormat::R16 => [u16_at(p), u16_at(p), u16_at(p), 1.0],
+            TextureFormat::RG16 => [u16_at(p * 2), u16_at(p * 2), u16_at(p * 2), u16_at(p * 2 + 1)],
+            TextureFormat::RGBA16 => [u16_at(p * 4), u16_at(p * 4 + 1), u16_at(p * 4 + 2), u16_at(p * 4 + 3)],
+            TextureFormat::RGBA32F => [f32_at(p * 4), f32_at(p * 4 + 1), f32_at(p * 4 + 2), f32_at(p * 4 + 3)],
+            TextureFormat::Block(_) => panic!("Block compressed images can't be put in an atlas!!"),
+        };
+        let mut out = [0u8; 4];
+        for (c, value) in pixel.iter().enumerate() {
+            // alpha is always linear
+            let value = if !srgb && c < 3 { linear_to_srgb(value.clamp(0.0, 1.0)) } else { *value };
+            out[c] = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+        out
+    }).collect()
+}
+
+// A skyline bin packer. This keeps track of the top edge of everything placed so far, and puts each rect where it
+// ends up lowest
+struct Skyline {
+    width: u32,
+    height: u32,
+    nodes: Vec<(u32, u32, u32)>,// x, y and width of each segment of the skyline
+}
+
+impl Skyline {
+
+    fn new(width: u32, height: u32) -> Self {
+        Self { width, height, nodes: vec![(0, 0, width)] }
+    }
+
+    // the y that a rect would sit at if its left edge was at the start of this node
+    fn fits(&self, node: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.nodes[node].0;
+        if x + width > self.width {
+            return None;
+        }
+        let mut y = 0;
+        let mut remaining = width as i64;
+        let mut i = node;
+        while remaining > 0 {
+            let (_, node_y, node_width) = *self.nodes.get(i)?;
+            y = y.max(node_y);
+            if y + height > self.height {
+                return None;
+            }
+            remaining -= node_width as i64;
+            i += 1;
+        }
+        Some(y)
+    }
+
+    fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        // the lowest place, then the leftmost
+        let (node, y) = (0..self.nodes.len())
+            .filter_map(|i| {self.fits(i, width, height).map(|y| {(i, y)})})
+            .min_by_key(|(i, y)| {(y + height, self.nodes[*i].0)})?;
+        let x = self.nodes[node].0;
+        self.nodes.insert(node, (x, y + height, width));
+
+        // cut the nodes that are now under the new one
+        let i = node + 1;
+        while i < self.nodes.len() {
+            let end = self.nodes[i - 1].0 + self.nodes[i - 1].2;
+            let (next_x, _, next_width) = self.nodes[i];
+            if next_x >= end {
+                break;
+            }
+            let shrink = end - next_x;
+            if next_width <= shrink {
+                self.nodes.remove(i);
+            }
+            else {
+                self.nodes[i].0 += shrink;
+                self.nodes[i].2 -= shrink;
+                break;
+            }
+        }
+
+        // join up the nodes at the same height
+        let mut i = 0;
+        while i + 1 < self.nodes.len() {
+            if self.nodes[i].1 == self.nodes[i + 1].1 {
+                self.nodes[i].2 += self.nodes[i + 1].2;
+                self.nodes.remove(i + 1);
+            }
+            else {
+                i += 1;
+            }
+        }
+        Some((x, y))
+    }
+}
@@ -0,0 +1,135 @@
+use std::path::PathBuf;
+use once_cell::sync::Lazy;
+
+// The file the engine config is read from, relative to the working directory. BLACK_ICE_CONFIG can point somewhere else
+pub const CONFIG_FILE: &str = "black_ice.cfg";
+
+static CONFIG: Lazy<EngineConfig> = Lazy::new(|| {EngineConfig::load()});
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MountType {
+    Folder,
+    Pack,
+}
+
+// An asset folder or pack, mounted as ASSET:<name>/...
+#[derive(Clone, Debug)]
+pub struct AssetMount {
+    pub name: String,
+    pub path: PathBuf,
+    pub mount_type: MountType,
+}
+
+impl AssetMount {
+
+    // works out the mount from the path alone, so assets/ is a folder mounted as ASSET:assets/... and patch.pkg
+    // is a pack mounted as ASSET:patch/...
+    pub fn from_path(path: PathBuf) -> Self {
+        let name = path.file_stem().map(|n| {n.to_string_lossy().to_string()}).unwrap_or(path.display().to_string());
+        let mount_type = if path.extension().map(|e| {e == "pkg"}).unwrap_or(false) { MountType::Pack } else { MountType::Folder };
+        Self { name, path, mount_type }
+    }
+}
+
+/** The engine config. This is read from black_ice.cfg, one setting per line (lines starting with # are ignored):
+*     app_dir = .
+*     folder assets = assets
+*     pack patch = packs/patch.pkg
+* Relative paths are relative to app_dir, which is the working directory by default. Each folder or pack is mounted with
+* the name given, so the folder above is loaded with ASSET:assets/... Mounts are loaded in the order they are listed.
+*
+* These can be overridden with environment variables:
+*     BLACK_ICE_CONFIG - the config file to read instead of black_ice.cfg
+*     BLACK_ICE_APP_DIR - replaces app_dir
+*     BLACK_ICE_ASSETS - replaces all of the mounts. This is a list of paths seperated like PATH is (: on linux and ; on windows),
+*                        where each one is either name=path or just a path, which is named after the folder or pack
+*
+* If nothing is mounted, the assets folder in app_dir is mounted as ASSET:assets/...
+*/
+#[derive(Clone, Debug)]
+pub struct EngineConfig {
+    pub app_dir: PathBuf,
+    pub mounts: Vec<AssetMount>,
+}
+
+impl EngineConfig {
+
+    pub fn get() -> &'static EngineConfig {
+        &CONFIG
+    }
+
+    pub fn load() -> Self {
+        let mut config = Self { app_dir: std::env::current_dir().unwrap_or(PathBuf::from(".")), mounts: vec![] };
+
+        let config_file = std::env::var_os("BLACK_ICE_CONFIG").map(PathBuf::from).unwrap_or(PathBuf::from(CONFIG_FILE));
+        if let Ok(text) = std::fs::read_to_string(&config_file) {
+            config.parse(text.as_str());
+        }
+        else if std::env::var_os("BLACK_ICE_CONFIG").is_some() {
+            println!("Failed to read the engine config {}!!", config_file.display());
+        }
+
+        if let Some(app_dir) = std::env::var_os("BLACK_ICE_APP_DIR") {
+            config.app_dir = PathBuf::from(app_dir);
+        }
+        if let Some(assets) = std::env::var_os("BLACK_ICE_ASSETS") {
+            config.mounts = std::env::split_paths(&assets)
+                .filter(|p| {!p.as_os_str().is_empty()})
+                .map(|p| {
+                    let s = p.to_str().expect("Asset mount paths must be valid unicode!!");
+                    match s.split_once('=') {
+                        Some((name, path)) => {
+                            let mut mount = AssetMount::from_path(PathBuf::from(path.trim()));
+                            mount.name = name.trim().to_string();
+                            mount
+                        },
+                        None => AssetMount::from_path(p.clone())
+                    }
+                })
+                .collect();
+        }
+
+        if config.mounts.is_empty() {
+            config.mounts.push(AssetMount::from_path(PathBuf::from("assets")));
+        }
+        // everything is relative to the app directory!!
+        for mount in config.mounts.iter_mut() {
+            if mount.path.is_relative() {
+                mount.path = config.app_dir.join(&mount.path);
+            }
+        }
+        config
+    }
+
+    fn parse(&mut self, text: &str) {
+        for line in text.lines().map(|l| {l.trim()}).filter(|l| {!l.is_empty() && !l.starts_with('#')}) {
+            let (key, value) = match line.split_once('=') {
+                Some((k, v)) => (k.trim(), v.trim()),
+                None => {
+                    println!("Ignoring engine config line \"{}\", settings must be key = value!!", line);
+                    continue;
+                }
+            };
+            let mut words = key.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("app_dir"), None) => self.app_dir = PathBuf::from(value),
+                (Some("folder"), name) | (Some("pack"), name) => {
+                    let mut mount = AssetMount::from_path(PathBuf::from(value));
+                    mount.mount_type = if key.starts_with("pack") { MountType::Pack } else { MountType::Folder };
+                    if let Some(name) = name {
+                        mount.name = name.to_string();
+                    }
+                    self.mounts.push(mount);
+                },
+                _ => println!("Unknown engine config setting {}!!", key)
+            }
+        }
+    }
+
+    // where the first asset folder lives, for anything that still reads assets straight from disk
+    pub fn asset_dir(&self) -> PathBuf {
+        self.mounts.iter().find(|m| {m.mount_type == MountType::Folder})
+            .map(|m| {m.path.clone()})
+            .unwrap_or(self.app_dir.join("assets"))
+    }
+}
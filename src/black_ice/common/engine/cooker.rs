@@ -0,0 +1,62 @@
+use std::{io, path::PathBuf};
+
+use super::asset_mgr::{AssetFolder, AssetPack, PathRep};
+use super::asset_types::{shader_asset::Shader, texture::Texture};
+
+/** The asset cooker. This turns a source asset folder into a pack that is ready to ship, so that the game doesn't need to
+* do the slow work every time it is launched:
+*     Shaders (.shad, .vert, .frag, .comp) are compiled to SPIR-V and written out as .shad files with is_compiled: true.
+*     Images are decoded and written out in their own pixel format with the ext "raw", and the format, width, height
+*     and mips in the metadata. 16 bit and float pixels are written little endian. Block compressed DDS files are
+*     already ready for the GPU, so they are left alone.
+* Everything else (including .glsl includes and hlsl shaders) is copied into the pack as is. The paths of the cooked files
+* stay the same, so nothing that loads them needs to change!!
+*/
+pub fn cook_folder(folder: &AssetFolder, out: PathBuf) -> Result<(), io::Error> {
+    // the render system isn't running, so we need to gather the shaders that can be included ourselves
+    let mut context = glsl_include::Context::new();
+    for (_, rep) in folder.entries() {
+        if rep.get_meta_data().get("type").map(|t| {t.as_str()}) != Some("Shader") {
+            continue;
+        }
+        let (data, _) = AssetPack::read_entry(&rep.name, rep)?;
+        if let Ok(text) = String::from_utf8(data) {
+            context.include(rep.name.clone(), text);
+        }
+    }
+
+    AssetPack::build_with(folder, out, None, &|rep_path: &String, rep: &PathRep| {
+        let (data, mut meta_data) = AssetPack::read_entry(rep_path, rep)?;
+        let ext = meta_data.get("ext").cloned().unwrap_or_default();
+        match meta_data.get("type").map(|t| {t.as_str()}) {
+            Some("Shader") => {
+                let file_name = rep.name[..rep.name.find('.').unwrap_or(rep.name.len())].to_string();
+                match Shader::cook(&data, ext.as_str(), &file_name, &context) {
+                    Some(cooked) => {
+                        println!("Cooked shader {}", rep_path);
+                        meta_data.insert("ext".to_string(), "shad".to_string());
+                        meta_data.insert("cooked".to_string(), "true".to_string());
+                        Ok((cooked, meta_data))
+                    },
+                    None => Ok((data, meta_data))
+                }
+            },
+            Some("Image") if ext != "raw" && !Texture::is_block_compressed(ext.as_str()) => {
+                let image = Texture::decode(&data, &meta_data)?;
+                println!("Cooked texture {}", rep_path);
+                meta_data.insert("ext".to_string(), "raw".to_string());
+                meta_data.insert("format".to_string(), image.get_format().as_str().to_string());
+                meta_data.insert("width".to_string(), image.get_width().to_string());
+                meta_data.insert("height".to_string(), image.get_height().to_string());
+                meta_data.insert("mips".to_string(), image.get_mip_count().to_string());
+                meta_data.insert("srgb".to_string(), image.is_srgb().to_string());
+                meta_data.insert("cooked".to_string(), "true".to_string());
+                let mut pixels = image.get_data().to_vec();
+                Texture::swap_le_bytes(&mut pixels, image.get_format());
+                Ok((pixels, meta_data))
+            },
+            _ => Ok((data, meta_data))
+        }
+    })
+}
+
@@ -14,6 +14,7 @@ use crate::black_ice::common::{*, mesh::Mesh, components::{component_system::*,
 use std::sync::Arc;
 use colored::Colorize;
 use engine::asset_mgr::{self, AssetManager};
+use engine::config::{EngineConfig, MountType};
 use engine::input::Input;
 use parking_lot::*;
 use futures::join;
@@ -372,10 +373,18 @@ impl Env {
         // Set up thread pool
 
         
-        // load in our asset folders
-        
-        #[cfg(target_os="linux")]AssetManager::load_asset_folder("/home/detrix/rust/black-ice/assets".to_string());
-        #[cfg(target_os="windows")]AssetManager::load_asset_folder("F:/Rust/Program 1/assets".to_string());
+        // load in our asset folders and packs, these are set in the engine config (see engine::config)
+        for mount in &EngineConfig::get().mounts {
+            let path = mount.path.to_string_lossy().to_string();
+            let mounted = match mount.mount_type {
+                MountType::Folder => AssetManager::mount_asset_folder(mount.name.clone(), path.clone()),
+                MountType::Pack => AssetManager::mount_asset_pack(mount.name.clone(), path.clone()),
+            };
+            // a missing mount shouldn't stop the game, its assets just won't be found
+            if let Err(e) = mounted {
+                println!("Skipping asset mount {} ({})!! {}", mount.name, path, e);
+            }
+        }
         
         // we will get the environment while we still can !!
         // we do not want to constantly own the environment so that other threads can access it safely!!
@@ -476,6 +485,7 @@ impl Env {
                 EventSystem::processing(Env::get_event_sys().clone());
                 InputSystem::processing(Env::get_input_sys().clone());
                 EntitySystem::processing(Env::get_entity_sys().clone());
+                AssetManager::processing();
             }
         }
 
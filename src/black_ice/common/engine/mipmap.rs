@@ -0,0 +1,203 @@
+use std::f32::consts::PI;
+
+use super::pipeline::{Image, TextureFormat};
+
+// The filters that mip levels can be made with. Box is quick, Kaiser is sharper and is what you want for anything that
+// will be seen from far away
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MipFilter {
+    Box,
+    Kaiser,
+}
+
+impl MipFilter {
+
+    // the "mipmaps" metadata of a texture. This is none, box or kaiser
+    pub fn from_meta(value: &str) -> Option<Self> {
+        match value {
+            "box" => Some(MipFilter::Box),
+            "kaiser" => Some(MipFilter::Kaiser),
+            _ => None
+        }
+    }
+}
+
+// how many destination texels either side of the centre the kaiser filter looks at, and how sharp it is
+const KAISER_WIDTH: f32 = 3.0;
+const KAISER_ALPHA: f32 = 4.0;
+
+// the number of mip levels down to 1x1
+pub fn mip_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/** Makes the full mip chain of an image on the CPU, replacing any mip levels it already had. Block compressed images
+* can't be filtered, so they are left alone (DDS files should have their mips made when they are exported)!!
+* sRGB images are filtered in linear space, otherwise the smaller levels get darker.
+*/
+pub fn generate_mips(image: &mut Image, filter: MipFilter) {
+    let format = image.get_format();
+    let channels = match channel_count(format) {
+        Some(c) => c,
+        None => return
+    };
+    let (width, height) = (image.get_width(), image.get_height());
+    let srgb = image.is_srgb();
+    let count = mip_count(width, height);
+
+    let base = image.get_mip(0).expect("Image has no data!!").2;
+    let mut level = read_level(base, format, channels, srgb);
+    let mut data = base.to_vec();
+    let (mut w, mut h) = (width, height);
+    for _ in 1..count {
+        let (next_w, next_h) = ((w / 2).max(1), (h / 2).max(1));
+        level = match filter {
+            MipFilter::Box => box_filter(&level, w, h, next_w, next_h, channels),
+            MipFilter::Kaiser => {
+                let horizontal = kaiser_filter(&level, w, h, next_w, channels, true);
+                kaiser_filter(&horizontal, next_w, h, next_h, channels, false)
+            }
+        };
+        data.extend(write_level(&level, format, channels, srgb));
+        (w, h) = (next_w, next_h);
+    }
+    image.set_data(data, count);
+}
+
+fn channel_count(format: TextureFormat) -> Option<usize> {
+    match format {
+        TextureFormat::R8 | TextureFormat::R16 => Some(1),
+        TextureFormat::RG8 | TextureFormat::RG16 => Some(2),
+        TextureFormat::RGB8 => Some(3),
+        TextureFormat::RGBA8 | TextureFormat::RGBA16 | TextureFormat::RGBA32F => Some(4),
+        TextureFormat::Block(_) => None,
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+// reads a level into floats, one per channel
+fn read_level(data: &[u8], format: TextureFormat, channels: usize, srgb: bool) -> Vec<f32> {
+    let mut values: Vec<f32> = match format {
+        TextureFormat::R8 | TextureFormat::RG8 | TextureFormat::RGB8 | TextureFormat::RGBA8 => {
+            data.iter().map(|c| {*c as f32 / 255.0}).collect()
+        },
+        TextureFormat::R16 | TextureFormat::RG16 | TextureFormat::RGBA16 => {
+            data.chunks_exact(2).map(|c| {u16::from_ne_bytes([c[0], c[1]]) as f32 / 65535.0}).collect()
+        },
+        _ => data.chunks_exact(4).map(|c| {f32::from_ne_bytes([c[0], c[1], c[2], c[3]])}).collect()
+    };
+    if srgb {
+        // alpha is always linear!!
+        for (i, v) in values.iter_mut().enumerate() {
+            if i % channels < 3 {
+                *v = srgb_to_linear(*v);
+            }
+        }
+    }
+    values
+}
+
+fn write_level(values: &[f32], format: TextureFormat, channels: usize, srgb: bool) -> Vec<u8> {
+    let values = values.iter().enumerate().map(|(i, v)| {
+        if srgb && i % channels < 3 { linear_to_srgb(*v) } else { *v }
+    });
+    match format {
+        TextureFormat::R8 | TextureFormat::RG8 | TextureFormat::RGB8 | TextureFormat::RGBA8 => {
+            values.map(|v| {(v.clamp(0.0, 1.0) * 255.0).round() as u8}).collect()
+        },
+        TextureFormat::R16 | TextureFormat::RG16 | TextureFormat::RGBA16 => {
+            values.flat_map(|v| {((v.clamp(0.0, 1.0) * 65535.0).round() as u16).to_ne_bytes()}).collect()
+        },
+        _ => values.flat_map(|v| {v.to_ne_bytes()}).collect()
+    }
+}
+
+// averages each 2x2 square. Odd sizes just use the last row or column again
+fn box_filter(level: &[f32], width: u32, height: u32, next_w: u32, next_h: u32, channels: usize) -> Vec<f32> {
+    let (width, height) = (width as usize, height as usize);
+    let mut result = vec![0.0; next_w as usize * next_h as usize * channels];
+    for y in 0..next_h as usize {
+        let (y0, y1) = ((y * 2).min(height - 1), (y * 2 + 1).min(height - 1));
+        for x in 0..next_w as usize {
+            let (x0, x1) = ((x * 2).min(width - 1), (x * 2 + 1).min(width - 1));
+            for c in 0..channels {
+                let sum = level[(y0 * width + x0) * channels + c] + level[(y0 * width + x1) * channels + c]
+                    + level[(y1 * width + x0) * channels + c] + level[(y1 * width + x1) * channels + c];
+                result[(y * next_w as usize + x) * channels + c] = sum / 4.0;
+            }
+        }
+    }
+    result
+}
+
+// the zeroth order modified bessel function, which the kaiser window is made from
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..32 {
+        term *= (half / k as f32) * (half / k as f32);
+        sum += term;
+        if term < sum * 1e-7 {
+            break;
+        }
+    }
+    sum
+}
+
+fn kaiser_weight(d: f32) -> f32 {
+    let t = d / KAISER_WIDTH;
+    if t.abs() >= 1.0 {
+        return 0.0;
+    }
+    let sinc = if d == 0.0 { 1.0 } else { (PI * d).sin() / (PI * d) };
+    sinc * bessel_i0(KAISER_ALPHA * (1.0 - t * t).sqrt()) / bessel_i0(KAISER_ALPHA)
+}
+
+// halves the image along one direction with a kaiser windowed sinc. Run it once in each direction!!
+fn kaiser_filter(level: &[f32], width: u32, height: u32, next: u32, channels: usize, horizontal: bool) -> Vec<f32> {
+    let (width, height) = (width as usize, height as usize);
+    let (src_len, lines) = if horizontal { (width, height) } else { (height, width) };
+    let next = next as usize;
+    let (out_w, out_h) = if horizontal { (next, height) } else { (width, next) };
+    let mut result = vec![0.0; out_w * out_h * channels];
+
+    // the source is shrunk by this much, which is 2 unless the size is already 1
+    let scale = src_len as f32 / next as f32;
+    let radius = (KAISER_WIDTH * scale).ceil() as isize;
+    for i in 0..next {
+        // the weights are the same for every line, so work them out once
+        let centre = (i as f32 + 0.5) * scale;
+        let mut taps: Vec<(usize, f32)> = vec![];
+        let mut total = 0.0;
+        for s in (centre as isize - radius)..=(centre as isize + radius) {
+            let weight = kaiser_weight((s as f32 + 0.5 - centre) / scale);
+            if weight == 0.0 {
+                continue;
+            }
+            // clamp to the edge of the image
+            taps.push((s.clamp(0, src_len as isize - 1) as usize, weight));
+            total += weight;
+        }
+
+        for line in 0..lines {
+            for c in 0..channels {
+                let mut value = 0.0;
+                for (s, weight) in &taps {
+                    let index = if horizontal { line * width + s } else { s * width + line };
+                    value += level[index * channels + c] * weight;
+                }
+                let out = if horizontal { line * out_w + i } else { i * out_w + line };
+                result[out * channels + c] = value / total;
+            }
+        }
+    }
+    result
+}
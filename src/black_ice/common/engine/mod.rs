@@ -10,6 +10,10 @@ pub mod event_system;
 pub mod asset_mgr;
 pub mod asset_types;
 pub mod engine_server;
+pub mod config;
+pub mod cooker;
+pub mod mipmap;
+pub mod atlas;
 
 // pub const NULLPTR: *const std::ffi::c_void = std::ptr::null::<std::ffi::c_void>();
 // pub const NULLPTR_MUT: *mut std::ffi::c_void = std::ptr::null::<std::ffi::c_void>().cast_mut();
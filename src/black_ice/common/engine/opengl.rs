@@ -10,11 +10,19 @@ use engine::asset_types::shader_asset::ShaderLang;
 use gl46::*;
 use image::EncodableLayout;
 use sdl2::{video::GLContext, surface};
-use crate::black_ice::common::{angles::{QuatConstructor, Quat}, engine::pipeline::RenderPipelineSystem, matrices::*, mesh::Mesh, vertex::*, *};
+use crate::black_ice::common::{angles::{QuatConstructor, Quat}, engine::pipeline::RenderPipelineSystem, matrices::*, mesh::{IndexBuffer, Mesh, VertexAttribute}, vertex::*, *};
 use parking_lot::*;
-use self::engine::asset_types::{shader_asset::ShaderType, materials::*};
+use self::engine::asset_types::{shader_asset::{ShaderType, ShaderStageDescriptor, VariableDescriptorEnum}, materials::*};
 
-use super::pipeline::{Pipeline, Camera, Data};
+use super::pipeline::{Pipeline, Camera, Data, Image, TextureFormat, BlockCompression, SamplerSettings, WrapMode, FilterMode};
+
+// the S3TC formats are from an extension, so gl46 doesn't have them
+const GL_COMPRESSED_RGBA_S3TC_DXT1_EXT: GLenum = GLenum(0x83F1);
+const GL_COMPRESSED_RGBA_S3TC_DXT3_EXT: GLenum = GLenum(0x83F2);
+const GL_COMPRESSED_RGBA_S3TC_DXT5_EXT: GLenum = GLenum(0x83F3);
+const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT: GLenum = GLenum(0x8C4D);
+const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT: GLenum = GLenum(0x8C4E);
+const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: GLenum = GLenum(0x8C4F);
 pub struct SdlGlContext(GLContext);
 
 unsafe impl Send for SdlGlContext{}
@@ -44,7 +52,7 @@ pub struct DriverValues {
     pub gl_context: Option<SdlGlContext>,
     pub shader_stages: Vec<(String, u32)>,
     pub gl: Option<GlFns>,
-    
+    pub textures: HashMap<String, u32>,// the gl textures of the registered images
 }
 
 #[derive(Clone)]
@@ -90,6 +98,13 @@ impl OGlRender for Pipeline {
             
             let mut pipeline = th.lock();
             let cameras = pipeline.cameras.clone();
+            // the programs are made here as this is the thread with the context. Shaders without any stages can't be drawn
+            let draws: Vec<(u32, Option<ShaderStageDescriptor>, Vec<Data>)> = std::mem::take(&mut pipeline.draws).into_iter()
+                .filter(|(shader, _)| {!shader.shader_stages.is_empty()})
+                .map(|(shader, data)| {
+                    let (program, _, descriptor) = pipeline.register_shader_program_gl(shader);
+                    (program, descriptor, data)
+                }).collect();
             let mut p_driver = pipeline.driver.clone();
             let mut d = p_driver.lock();
             let mut driver = d.as_mut().unwrap();
@@ -119,6 +134,12 @@ impl OGlRender for Pipeline {
                 gl.DepthMask(GL_FALSE.0 as u8);
                 gl.DepthFunc(GL_ALWAYS);
 
+                for (program, descriptor, data) in &draws {
+                    gl.UseProgram(*program);
+                    DriverValues::bind_textures(driver, descriptor.as_ref(), data);
+                    // TODO: draw the surface in data once its buffers are uploaded!!
+                }
+
                 let window = p_window.lock();
                 window.gl_swap_window();
             }
@@ -137,6 +158,7 @@ impl Default for DriverValues {
             gl_context: None,
             shader_stages: Vec::new(),
             gl: None,
+            textures: HashMap::new(),
         }
     }
 }
@@ -193,6 +215,144 @@ impl DriverValues {
 
     }
 
+    // gives back the internal format, the pixel format and the pixel type to upload an uncompressed image with
+    fn get_texture_format(format: TextureFormat, srgb: bool) -> (GLenum, GLenum, GLenum) {
+        match format {
+            TextureFormat::R8 => (GL_R8, GL_RED, GL_UNSIGNED_BYTE),
+            TextureFormat::RG8 => (GL_RG8, GL_RG, GL_UNSIGNED_BYTE),
+            TextureFormat::RGB8 => (if srgb { GL_SRGB8 } else { GL_RGB8 }, GL_RGB, GL_UNSIGNED_BYTE),
+            TextureFormat::RGBA8 => (if srgb { GL_SRGB8_ALPHA8 } else { GL_RGBA8 }, GL_RGBA, GL_UNSIGNED_BYTE),
+            TextureFormat::R16 => (GL_R16, GL_RED, GL_UNSIGNED_SHORT),
+            TextureFormat::RG16 => (GL_RG16, GL_RG, GL_UNSIGNED_SHORT),
+            TextureFormat::RGBA16 => (GL_RGBA16, GL_RGBA, GL_UNSIGNED_SHORT),
+            TextureFormat::RGBA32F => (GL_RGBA32F, GL_RGBA, GL_FLOAT),
+            TextureFormat::Block(_) => panic!("Block compressed images need to be uploaded with get_block_format!!"),
+        }
+    }
+
+    fn get_block_format(format: BlockCompression, srgb: bool) -> GLenum {
+        match format {
+            BlockCompression::BC1 => if srgb { GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT } else { GL_COMPRESSED_RGBA_S3TC_DXT1_EXT },
+            BlockCompression::BC2 => if srgb { GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT } else { GL_COMPRESSED_RGBA_S3TC_DXT3_EXT },
+            BlockCompression::BC3 => if srgb { GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT } else { GL_COMPRESSED_RGBA_S3TC_DXT5_EXT },
+            BlockCompression::BC4 => GL_COMPRESSED_RED_RGTC1,
+            BlockCompression::BC4S => GL_COMPRESSED_SIGNED_RED_RGTC1,
+            BlockCompression::BC5 => GL_COMPRESSED_RG_RGTC2,
+            BlockCompression::BC5S => GL_COMPRESSED_SIGNED_RG_RGTC2,
+            BlockCompression::BC6H => GL_COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+            BlockCompression::BC6HS => GL_COMPRESSED_RGB_BPTC_SIGNED_FLOAT,
+            BlockCompression::BC7 => if srgb { GL_COMPRESSED_SRGB_ALPHA_BPTC_UNORM } else { GL_COMPRESSED_RGBA_BPTC_UNORM },
+        }
+    }
+
+    // uploads an image and all of its mip levels into a new texture, in the format that the image is stored in
+    pub unsafe fn create_texture(this: &mut Self, image: &Image) -> u32 {
+        let gl = this.gl.as_ref().unwrap();
+        let mut tex = 0;
+        gl.GenTextures(1, &mut tex);
+        gl.BindTexture(GL_TEXTURE_2D, tex);
+        // the rows of RGB8 and R8 images usually aren't 4 byte aligned!!
+        gl.PixelStorei(GL_UNPACK_ALIGNMENT, 1);
+        let mut levels = 0;
+        for level in 0..image.get_mip_count() {
+            let (width, height, data) = match image.get_mip(level) {
+                Some(mip) => mip,
+                None => break
+            };
+            match image.get_format() {
+                TextureFormat::Block(format) => {
+                    gl.CompressedTexImage2D(GL_TEXTURE_2D, level as i32, DriverValues::get_block_format(format, image.is_srgb()), width as i32, height as i32, 0, data.len() as i32, data.as_ptr().cast());
+                },
+                format => {
+                    let (internal_format, pixel_format, pixel_type) = DriverValues::get_texture_format(format, image.is_srgb());
+                    gl.TexImage2D(GL_TEXTURE_2D, level as i32, internal_format.0 as i32, width as i32, height as i32, 0, pixel_format, pixel_type, data.as_ptr().cast());
+                }
+            }
+            levels += 1;
+        }
+        gl.PixelStorei(GL_UNPACK_ALIGNMENT, 4);
+        gl.TexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAX_LEVEL, (levels - 1).max(0));
+
+        // one channel images are grey and two channel images are grey with alpha, not red and red green!!
+        match image.get_format() {
+            TextureFormat::R8 | TextureFormat::R16 => {
+                let swizzle = [GL_RED.0 as i32, GL_RED.0 as i32, GL_RED.0 as i32, GL_ONE.0 as i32];
+                gl.TexParameteriv(GL_TEXTURE_2D, GL_TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+            },
+            TextureFormat::RG8 | TextureFormat::RG16 => {
+                let swizzle = [GL_RED.0 as i32, GL_RED.0 as i32, GL_RED.0 as i32, GL_GREEN.0 as i32];
+                gl.TexParameteriv(GL_TEXTURE_2D, GL_TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+            },
+            _ => {}
+        }
+        DriverValues::set_sampler(this, &image.sampler);
+        tex
+    }
+
+    // makes the texture for a registered image, replacing the old one if it has been uploaded before
+    pub unsafe fn upload_image(this: &mut Self, name: String, image: &Image) {
+        if let Some(old) = this.textures.remove(&name) {
+            this.gl.as_ref().unwrap().DeleteTextures(1, &old);
+        }
+        let tex = DriverValues::create_texture(this, image);
+        this.textures.insert(name, tex);
+    }
+
+    pub fn get_texture(this: &Self, name: &String) -> Option<u32> {
+        this.textures.get(name).cloned()
+    }
+
+    // binds the textures in the data to the texture units of their samplers, for the program that is in use
+    pub unsafe fn bind_textures(this: &Self, descriptor: Option<&ShaderStageDescriptor>, data: &[Data]) {
+        let gl = this.gl.as_ref().unwrap();
+        for value in data {
+            if let Data::Texture(sampler, image_name) = value {
+                // with SPIR-V shaders the binding of a sampler is the unit that it reads from
+                let unit = match descriptor.map(|d| {d.get(sampler.clone())}) {
+                    Some(VariableDescriptorEnum::Variable(variable)) => variable.binding,
+                    _ => continue// the shader doesn't use this texture
+                };
+                if let Some(tex) = DriverValues::get_texture(this, image_name) {
+                    gl.ActiveTexture(GLenum(GL_TEXTURE0.0 + unit));
+                    gl.BindTexture(GL_TEXTURE_2D, tex);
+                }
+            }
+        }
+        gl.ActiveTexture(GL_TEXTURE0);
+    }
+
+    // sets how the currently bound texture is sampled
+    pub unsafe fn set_sampler(this: &mut Self, sampler: &SamplerSettings) {
+        let gl = this.gl.as_ref().unwrap();
+        let wrap = |mode: WrapMode| {
+            match mode {
+                WrapMode::Repeat => GL_REPEAT,
+                WrapMode::MirroredRepeat => GL_MIRRORED_REPEAT,
+                WrapMode::ClampToEdge => GL_CLAMP_TO_EDGE,
+                WrapMode::ClampToBorder => GL_CLAMP_TO_BORDER,
+            }.0 as i32
+        };
+        gl.TexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, wrap(sampler.wrap_u));
+        gl.TexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, wrap(sampler.wrap_v));
+
+        // in gl the mip filter is part of the min filter
+        let min_filter = match (sampler.min_filter, sampler.mip_filter) {
+            (FilterMode::Nearest, None) => GL_NEAREST,
+            (FilterMode::Linear, None) => GL_LINEAR,
+            (FilterMode::Nearest, Some(FilterMode::Nearest)) => GL_NEAREST_MIPMAP_NEAREST,
+            (FilterMode::Nearest, Some(FilterMode::Linear)) => GL_NEAREST_MIPMAP_LINEAR,
+            (FilterMode::Linear, Some(FilterMode::Nearest)) => GL_LINEAR_MIPMAP_NEAREST,
+            (FilterMode::Linear, Some(FilterMode::Linear)) => GL_LINEAR_MIPMAP_LINEAR,
+        };
+        let mag_filter = match sampler.mag_filter {
+            FilterMode::Nearest => GL_NEAREST,
+            FilterMode::Linear => GL_LINEAR,
+        };
+        gl.TexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, min_filter.0 as i32);
+        gl.TexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, mag_filter.0 as i32);
+        gl.TexParameterf(GL_TEXTURE_2D, GL_TEXTURE_MAX_ANISOTROPY, sampler.anisotropy);
+    }
+
     pub unsafe fn create_graphics_pipeline(this: &mut Self, stage: usize) -> PipelineValues {
         let shader_program = &this.shader_stages[stage];
         PipelineValues {  }
@@ -218,51 +378,11 @@ impl DriverValues {
     //     return data;
     // }
 
-    pub unsafe fn create_buffer_vec_norm_tex(this: &mut Self, verts: &[f32], indices: &[u32]) -> (u32, u32, u32){
-        let gl = this.gl.as_ref().unwrap();
-
-        let mut vao = {
-                let mut v = 0;
-                gl.CreateVertexArrays(1, &mut v);
-                assert_ne!(v, 0);
-                v
-        };
-        let mut vbo = {
-                let mut v = 0;
-                gl.GenBuffers(1, &mut v);
-                assert_ne!(v, 0);
-                v
-        };
-        let mut elem_buffer = {
-                let mut v = 0;
-                gl.GenBuffers(1, &mut v);
-                assert_ne!(v, 0);
-                v
-        };
-
-        gl.BindVertexArray(vao);
-
-
-        gl.BindBuffer(GL_ARRAY_BUFFER, vbo);
-        
-        gl.BufferData(GL_ARRAY_BUFFER, size_of_val(verts) as isize, verts.as_ptr().cast(), GL_STATIC_DRAW);
-        gl.VertexAttribPointer(0, 3, GL_FLOAT, GL_FALSE.0 as u8, 8 * size_of::<f32>() as i32, 0 as *const _);
-        gl.EnableVertexAttribArray(0);
-        gl.VertexAttribPointer(1, 3, GL_FLOAT, GL_TRUE.0 as u8, (8 * size_of::<f32>()) as i32, (3 * size_of::<f32>()) as *const _);
-        gl.EnableVertexAttribArray(1);
-        gl.VertexAttribPointer(2, 2, GL_FLOAT, GL_FALSE.0 as u8, (8 * size_of::<f32>()) as i32, (6 * size_of::<f32>()) as *const _);
-        gl.EnableVertexAttribArray(2);
-
-
-
-        gl.BindBuffer(GL_ELEMENT_ARRAY_BUFFER, elem_buffer);
-        gl.BufferData(GL_ELEMENT_ARRAY_BUFFER, (indices.len() * size_of::<f32>()) as isize, indices.as_ptr().cast(), GL_STATIC_DRAW);
-
-        (vao, vbo, elem_buffer)
-        
-    }
-
-    pub unsafe fn create_buffer_vec_norm(this: &mut Self, verts: &[f32], indices: &[u32]) -> (u32, u32, u32){
+    /** Makes the vertex array for interleaved verts with the attributes in the layout, and uploads the indices as
+    * u16 or u32 depending on the buffer. Each attribute goes to its own location (see VertexAttribute::location), so
+    * the same shader works whatever else the verts have.
+    */
+    pub unsafe fn create_buffer_layout(this: &mut Self, verts: &[f32], layout: &[VertexAttribute], indices: &IndexBuffer) -> (u32, u32, u32){
         let gl = this.gl.as_ref().unwrap();
 
         let mut vao = {
@@ -270,111 +390,58 @@ impl DriverValues {
             gl.CreateVertexArrays(1, &mut v);
             assert_ne!(v, 0);
             v
-    };
-    let mut vbo = {
+        };
+        let mut vbo = {
             let mut v = 0;
             gl.GenBuffers(1, &mut v);
             assert_ne!(v, 0);
             v
-    };
-    let mut elem_buffer = {
+        };
+        let mut elem_buffer = {
             let mut v = 0;
             gl.GenBuffers(1, &mut v);
             assert_ne!(v, 0);
             v
-    };
+        };
 
         gl.BindVertexArray(vao);
 
 
         gl.BindBuffer(GL_ARRAY_BUFFER, vbo);
-
         gl.BufferData(GL_ARRAY_BUFFER, size_of_val(verts) as isize, verts.as_ptr().cast(), GL_STATIC_DRAW);
-        gl.VertexAttribPointer(0, 3, GL_FLOAT, GL_FALSE.0 as u8, 6 * size_of::<f32>() as i32, 0 as *const _);
-        gl.EnableVertexAttribArray(0);
-        gl.VertexAttribPointer(1, 3, GL_FLOAT, GL_TRUE.0 as u8, (6 * size_of::<f32>()) as i32, (3 * size_of::<f32>()) as *const _);
-        gl.EnableVertexAttribArray(1);
-
-
-        gl.BindBuffer(GL_ELEMENT_ARRAY_BUFFER, elem_buffer);
-        gl.BufferData(GL_ELEMENT_ARRAY_BUFFER, (indices.len() * size_of::<f32>()) as isize, indices.as_ptr().cast(), GL_STATIC_DRAW);
-
-        (vao, vbo, elem_buffer)
-    }
-
-    pub unsafe fn create_buffer_vec_tex(this: &mut Self, verts: &[f32], indices: &[u32]) -> (u32, u32, u32){
-        let gl = this.gl.as_ref().unwrap();
-
-        let mut vao = {
-            let mut v = 0;
-            gl.CreateVertexArrays(1, &mut v);
-            assert_ne!(v, 0);
-            v
-    };
-    let mut vbo = {
-            let mut v = 0;
-            gl.GenBuffers(1, &mut v);
-            assert_ne!(v, 0);
-            v
-    };
-    let mut elem_buffer = {
-            let mut v = 0;
-            gl.GenBuffers(1, &mut v);
-            assert_ne!(v, 0);
-            v
-    };
-
-        gl.BindVertexArray(vao);
-
 
-        gl.BufferData(GL_ARRAY_BUFFER, size_of_val(verts) as isize, verts.as_ptr().cast(), GL_STATIC_DRAW);
-        gl.VertexAttribPointer(0, 3, GL_FLOAT, GL_FALSE.0 as u8, 5 * size_of::<f32>() as i32, 0 as *const _);
-        gl.EnableVertexAttribArray(0);
-        gl.VertexAttribPointer(2, 2, GL_FLOAT, GL_TRUE.0 as u8, (5 * size_of::<f32>()) as i32, (3 * size_of::<f32>()) as *const _);
-        gl.EnableVertexAttribArray(2);
+        let stride: usize = layout.iter().map(|a| {a.size()}).sum();
+        let mut offset = 0;
+        for attribute in layout {
+            // normals are the only attribute that get normalized
+            let normalized = if *attribute == VertexAttribute::Normal { GL_TRUE } else { GL_FALSE };
+            gl.VertexAttribPointer(attribute.location(), attribute.size() as i32, GL_FLOAT, normalized.0 as u8, (stride * size_of::<f32>()) as i32, (offset * size_of::<f32>()) as *const _);
+            gl.EnableVertexAttribArray(attribute.location());
+            offset += attribute.size();
+        }
 
 
         gl.BindBuffer(GL_ELEMENT_ARRAY_BUFFER, elem_buffer);
-        gl.BufferData(GL_ELEMENT_ARRAY_BUFFER, (indices.len() * size_of::<f32>()) as isize, indices.as_ptr().cast(), GL_STATIC_DRAW);
+        let index_data = indices.as_bytes();
+        gl.BufferData(GL_ELEMENT_ARRAY_BUFFER, index_data.len() as isize, index_data.as_ptr().cast(), GL_STATIC_DRAW);
 
         (vao, vbo, elem_buffer)
     }
 
-    pub unsafe fn create_buffer_vec(this: &mut Self, verts: &[f32], indices: &[u32]) -> (u32, u32, u32){
-        let gl = this.gl.as_ref().unwrap();
-
-        let mut vao = {
-            let mut v = 0;
-            gl.CreateVertexArrays(1, &mut v);
-            assert_ne!(v, 0);
-            v
-    };
-    let mut vbo = {
-            let mut v = 0;
-            gl.GenBuffers(1, &mut v);
-            assert_ne!(v, 0);
-            v
-    };
-    let mut elem_buffer = {
-            let mut v = 0;
-            gl.GenBuffers(1, &mut v);
-            assert_ne!(v, 0);
-            v
-    };
-
-        gl.BindVertexArray(vao);
-
-
-        gl.BufferData(GL_ARRAY_BUFFER, size_of_val(verts) as isize, verts.as_ptr().cast(), GL_STATIC_DRAW);
-        gl.VertexAttribPointer(0, 3, GL_FLOAT, GL_FALSE.0 as u8, 3 * size_of::<f32>() as i32, 0 as *const _);
-        gl.EnableVertexAttribArray(0);
-
+    pub unsafe fn create_buffer_vec_norm_tex(this: &mut Self, verts: &[f32], indices: &IndexBuffer) -> (u32, u32, u32){
+        Self::create_buffer_layout(this, verts, &[VertexAttribute::Position, VertexAttribute::Normal, VertexAttribute::UV0], indices)
+    }
 
-        gl.BindBuffer(GL_ELEMENT_ARRAY_BUFFER, elem_buffer);
-        gl.BufferData(GL_ELEMENT_ARRAY_BUFFER, (indices.len() * size_of::<f32>()) as isize, indices.as_ptr().cast(), GL_STATIC_DRAW);
+    pub unsafe fn create_buffer_vec_norm(this: &mut Self, verts: &[f32], indices: &IndexBuffer) -> (u32, u32, u32){
+        Self::create_buffer_layout(this, verts, &[VertexAttribute::Position, VertexAttribute::Normal], indices)
+    }
 
+    pub unsafe fn create_buffer_vec_tex(this: &mut Self, verts: &[f32], indices: &IndexBuffer) -> (u32, u32, u32){
+        Self::create_buffer_layout(this, verts, &[VertexAttribute::Position, VertexAttribute::UV0], indices)
+    }
 
-        (vao, vbo, elem_buffer)
+    pub unsafe fn create_buffer_vec(this: &mut Self, verts: &[f32], indices: &IndexBuffer) -> (u32, u32, u32){
+        Self::create_buffer_layout(this, verts, &[VertexAttribute::Position], indices)
     }
 
     pub unsafe fn create_buffer_vec_singular(this: &mut Self, verts: &[f32]) -> (u32, u32, u32) {
@@ -52,6 +52,8 @@ pub enum Data {
     DVector(Vec3),
     MeshMatrix(Matrix34),
     Matrix(Matrix34),
+    Uniform(String, ShaderDataType),// a material param, with the name of the uniform it goes into
+    Texture(String, String),// a sampler, with the name of the registered image that is bound to it
 
 }
 
@@ -62,6 +64,7 @@ pub struct Pipeline {
     pub layer: u32,
     pub driver: Arc<Mutex<Option<DriverValues>>>,
     pub shaders: HashMap<String, (u32, Vec<u32>,Option<ShaderStageDescriptor>)>,
+    pub draws: Vec<(Shader, Vec<Data>)>,// what has been sent to be rendered since the last frame
     pub is_init: bool,
     counter: AtomicI32,
 }
@@ -86,7 +89,7 @@ impl Pipeline {
         #[cfg(feature="vulkan")] self.register_shader_program_vk(shader);
     }
     
-    fn register_shader_program_gl(&mut self, shader: Shader) -> (u32, Vec<u32>, Option<ShaderStageDescriptor>) {
+    pub fn register_shader_program_gl(&mut self, shader: Shader) -> (u32, Vec<u32>, Option<ShaderStageDescriptor>) {
         let mut shader_program: Option<(u32, Vec<u32>, Option<ShaderStageDescriptor>)> = self.shaders.get(&shader.asset_path).cloned();
         
         
@@ -337,21 +340,293 @@ impl Camera {
 
 }
 
+// The block compressed formats that we can load out of DDS files. These are never decoded on the CPU, the blocks are
+// given to the GPU as they are!!
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BlockCompression {
+    BC1,
+    BC2,
+    BC3,
+    BC4,
+    BC4S,// signed BC4 and BC5 are for things like normal maps that go from -1 to 1
+    BC5,
+    BC5S,
+    BC6H,
+    BC6HS,// signed half floats
+    BC7,
+}
+
+impl BlockCompression {
+
+    // the number of bytes in each 4x4 block
+    pub fn block_size(&self) -> usize {
+        match self {
+            BlockCompression::BC1 | BlockCompression::BC4 | BlockCompression::BC4S => 8,
+            _ => 16
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlockCompression::BC1 => "bc1",
+            BlockCompression::BC2 => "bc2",
+            BlockCompression::BC3 => "bc3",
+            BlockCompression::BC4 => "bc4",
+            BlockCompression::BC4S => "bc4s",
+            BlockCompression::BC5 => "bc5",
+            BlockCompression::BC5S => "bc5s",
+            BlockCompression::BC6H => "bc6h",
+            BlockCompression::BC6HS => "bc6hs",
+            BlockCompression::BC7 => "bc7",
+        }
+    }
+
+    pub fn from_str(format: &str) -> Option<Self> {
+        match format {
+            "bc1" => Some(BlockCompression::BC1),
+            "bc2" => Some(BlockCompression::BC2),
+            "bc3" => Some(BlockCompression::BC3),
+            "bc4" => Some(BlockCompression::BC4),
+            "bc4s" => Some(BlockCompression::BC4S),
+            "bc5" => Some(BlockCompression::BC5),
+            "bc5s" => Some(BlockCompression::BC5S),
+            "bc6h" => Some(BlockCompression::BC6H),
+            "bc6hs" => Some(BlockCompression::BC6HS),
+            "bc7" => Some(BlockCompression::BC7),
+            _ => None
+        }
+    }
+}
+
+// The formats that the data of an Image can be stored in. Images are kept in the format they were loaded in, so an 8 bit
+// png takes 4 bytes per pixel instead of being widened!!
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextureFormat {
+    R8,
+    RG8,
+    RGB8,
+    RGBA8,
+    R16,
+    RG16,
+    RGBA16,
+    RGBA32F,
+    Block(BlockCompression),
+}
+
+impl TextureFormat {
+
+    // the size of a pixel in bytes. Block compressed formats don't have one, use size_of instead!!
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            TextureFormat::R8 => 1,
+            TextureFormat::RG8 | TextureFormat::R16 => 2,
+            TextureFormat::RGB8 => 3,
+            TextureFormat::RGBA8 | TextureFormat::RG16 => 4,
+            TextureFormat::RGBA16 => 8,
+            TextureFormat::RGBA32F => 16,
+            TextureFormat::Block(_) => 0,
+        }
+    }
+
+    // the size of each channel in bytes, which is what the byte order matters for. Block compressed data is just bytes
+    pub fn bytes_per_channel(&self) -> usize {
+        match self {
+            TextureFormat::R16 | TextureFormat::RG16 | TextureFormat::RGBA16 => 2,
+            TextureFormat::RGBA32F => 4,
+            _ => 1,
+        }
+    }
+
+    // the number of bytes that a single image (or mip level) of this size takes up
+    pub fn size_of(&self, width: u32, height: u32) -> usize {
+        match self {
+            TextureFormat::Block(format) => (width.max(1) as usize + 3) / 4 * ((height.max(1) as usize + 3) / 4) * format.block_size(),
+            _ => width as usize * height as usize * self.bytes_per_pixel()
+        }
+    }
+
+    // these can be stored as sRGB
+    pub fn is_colour(&self) -> bool {
+        match self {
+            TextureFormat::RGB8 | TextureFormat::RGBA8 => true,
+            TextureFormat::Block(format) => matches!(format, BlockCompression::BC1 | BlockCompression::BC2 | BlockCompression::BC3 | BlockCompression::BC7),
+            _ => false
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TextureFormat::R8 => "r8",
+            TextureFormat::RG8 => "rg8",
+            TextureFormat::RGB8 => "rgb8",
+            TextureFormat::RGBA8 => "rgba8",
+            TextureFormat::R16 => "r16",
+            TextureFormat::RG16 => "rg16",
+            TextureFormat::RGBA16 => "rgba16",
+            TextureFormat::RGBA32F => "rgba32f",
+            TextureFormat::Block(format) => format.as_str(),
+        }
+    }
+
+    pub fn from_str(format: &str) -> Option<Self> {
+        match format {
+            "r8" => Some(TextureFormat::R8),
+            "rg8" => Some(TextureFormat::RG8),
+            "rgb8" => Some(TextureFormat::RGB8),
+            "rgba8" => Some(TextureFormat::RGBA8),
+            "r16" => Some(TextureFormat::R16),
+            "rg16" => Some(TextureFormat::RG16),
+            "rgba16" => Some(TextureFormat::RGBA16),
+            "rgba32f" => Some(TextureFormat::RGBA32F),
+            _ => BlockCompression::from_str(format).map(|f| {TextureFormat::Block(f)})
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WrapMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    ClampToBorder,
+}
+
+impl WrapMode {
+    pub fn from_str(mode: &str) -> Option<Self> {
+        match mode {
+            "repeat" => Some(WrapMode::Repeat),
+            "mirror" => Some(WrapMode::MirroredRepeat),
+            "clamp" => Some(WrapMode::ClampToEdge),
+            "border" => Some(WrapMode::ClampToBorder),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FilterMode {
+    Nearest,
+    Linear,
+}
+
+impl FilterMode {
+    pub fn from_str(mode: &str) -> Option<Self> {
+        match mode {
+            "nearest" => Some(FilterMode::Nearest),
+            "linear" => Some(FilterMode::Linear),
+            _ => None
+        }
+    }
+}
+
+/** How a texture is sampled. These are read from the metadata of the texture (or its .meta file):
+*     wrap, wrap_u, wrap_v - repeat, mirror, clamp or border. wrap sets both
+*     min_filter, mag_filter - nearest or linear
+*     mip_filter - nearest, linear or none (which only ever uses the first level)
+*     anisotropy - the max anisotropy, 1 turns it off
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SamplerSettings {
+    pub wrap_u: WrapMode,
+    pub wrap_v: WrapMode,
+    pub min_filter: FilterMode,
+    pub mag_filter: FilterMode,
+    pub mip_filter: Option<FilterMode>,
+    pub anisotropy: f32,
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self { 
+            wrap_u: WrapMode::Repeat, 
+            wrap_v: WrapMode::Repeat, 
+            min_filter: FilterMode::Linear, 
+            mag_filter: FilterMode::Linear, 
+            mip_filter: Some(FilterMode::Linear), 
+            anisotropy: 1.0 
+        }
+    }
+}
+
+impl SamplerSettings {
+    pub fn from_meta(metadata: &HashMap<String, String>) -> Self {
+        let mut settings = Self::default();
+        let get = |key: &str| {metadata.get(key).map(|v| {v.trim().to_ascii_lowercase()})};
+        if let Some(wrap) = get("wrap").and_then(|w| {WrapMode::from_str(&w)}) {
+            settings.wrap_u = wrap;
+            settings.wrap_v = wrap;
+        }
+        if let Some(wrap) = get("wrap_u").and_then(|w| {WrapMode::from_str(&w)}) {
+            settings.wrap_u = wrap;
+        }
+        if let Some(wrap) = get("wrap_v").and_then(|w| {WrapMode::from_str(&w)}) {
+            settings.wrap_v = wrap;
+        }
+        if let Some(filter) = get("min_filter").and_then(|f| {FilterMode::from_str(&f)}) {
+            settings.min_filter = filter;
+        }
+        if let Some(filter) = get("mag_filter").and_then(|f| {FilterMode::from_str(&f)}) {
+            settings.mag_filter = filter;
+        }
+        if let Some(filter) = get("mip_filter") {
+            settings.mip_filter = FilterMode::from_str(&filter);
+        }
+        if let Some(anisotropy) = get("anisotropy").and_then(|a| {a.parse::<f32>().ok()}) {
+            settings.anisotropy = anisotropy.max(1.0);
+        }
+        settings
+    }
+}
+
+// Where an image has been put in an atlas, in pixels and in UVs
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AtlasRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+}
+
+impl AtlasRegion {
+    // the whole of an image, which is what textures that aren't in an atlas use
+    pub fn full(width: u32, height: u32) -> Self {
+        Self { x: 0, y: 0, width, height, uv_min: [0.0, 0.0], uv_max: [1.0, 1.0] }
+    }
+
+    // maps a uv of the source image (0 to 1) into the atlas
+    pub fn map_uv(&self, uv: [f32; 2]) -> [f32; 2] {
+        [
+            self.uv_min[0] + (self.uv_max[0] - self.uv_min[0]) * uv[0],
+            self.uv_min[1] + (self.uv_max[1] - self.uv_min[1]) * uv[1],
+        ]
+    }
+}
+
 pub struct Image {
-    data: Vec<[u32;4]>,
+    data: Vec<u8>,// tightly packed in the format of the image, with every mip level one after the other
+    format: TextureFormat,
+    srgb: bool,
+    mip_count: u32,
+    pub sampler: SamplerSettings,
     width: u32,
     height: u32,
     pub is_atlas: bool,
     pub is_full: bool,
     max_width: u32,
     max_height: u32,
-    uvs: HashMap<String, (u32, u32)>,
+    uvs: HashMap<String, AtlasRegion>,// the images packed into this one, if it is an atlas
 }
 
 impl Image {
-    pub fn new(im_data: Vec<[u32;4]>, width: u32, height: u32, is_atlas: bool) -> Self {
+    pub fn new(im_data: Vec<u8>, format: TextureFormat, width: u32, height: u32, is_atlas: bool) -> Self {
         Self { 
             data: im_data, 
+            format: format,
+            srgb: false,
+            mip_count: 1,
+            sampler: SamplerSettings::default(),
             width: width, 
             height: height, 
             is_atlas: is_atlas, 
@@ -361,6 +636,82 @@ impl Image {
             uvs: HashMap::new(),
         }
     }
+
+    pub fn get_data(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+
+    pub fn get_format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        self.srgb
+    }
+
+    // only colour formats can be sRGB, this is ignored for anything else
+    pub fn set_srgb(&mut self, srgb: bool) {
+        self.srgb = srgb && self.format.is_colour();
+    }
+
+    pub fn get_mip_count(&self) -> u32 {
+        self.mip_count
+    }
+
+    // the data needs to hold this many mip levels!!
+    pub fn set_mip_count(&mut self, mip_count: u32) {
+        self.mip_count = mip_count.max(1);
+    }
+
+    // replaces the data, which has to be the same size and format as before, e.g. when making mips
+    pub fn set_data(&mut self, data: Vec<u8>, mip_count: u32) {
+        self.data = data;
+        self.set_mip_count(mip_count);
+    }
+
+    pub fn get_max_size(&self) -> (u32, u32) {
+        (self.max_width, self.max_height)
+    }
+
+    pub fn set_max_size(&mut self, max_width: u32, max_height: u32) {
+        self.max_width = max_width;
+        self.max_height = max_height;
+    }
+
+    // the region of an image that has been packed into this atlas
+    pub fn get_region(&self, name: &String) -> Option<AtlasRegion> {
+        self.uvs.get(name).cloned()
+    }
+
+    pub fn set_region(&mut self, name: String, region: AtlasRegion) {
+        self.uvs.insert(name, region);
+    }
+
+    pub fn get_regions(&self) -> &HashMap<String, AtlasRegion> {
+        &self.uvs
+    }
+
+    // gives back the size and the data of a mip level
+    pub fn get_mip(&self, level: u32) -> Option<(u32, u32, &[u8])> {
+        if level >= self.mip_count {
+            return None;
+        }
+        let mut offset = 0;
+        for l in 0..level {
+            offset += self.format.size_of((self.width >> l).max(1), (self.height >> l).max(1));
+        }
+        let (width, height) = ((self.width >> level).max(1), (self.height >> level).max(1));
+        let size = self.format.size_of(width, height);
+        self.data.get(offset..offset + size).map(|d| {(width, height, d)})
+    }
 }
 
 pub struct Did {
@@ -408,6 +759,7 @@ pub struct RenderPipelineSystem {
     pub sdl: Arc<Mutex<sdl2::Sdl>>,
     shader_stages_data: Vec<ShaderStage>,
     registered_images: HashMap<String, Arc<Mutex<Image>>>,
+    pending_images: Vec<String>,// images that need uploading on the render thread
     pub registered_shaders: HashMap<String, (String, Vec<u8>)>,
     pub shader_programs:HashMap<String, (u32, Vec<u32>)>,
     input_data: HashMap<u32, Data>
@@ -432,6 +784,7 @@ impl RenderPipelineSystem{
             is_init: false,
             counter: AtomicI32::new(0),
             shaders: HashMap::new(),
+            draws: vec![],
         }));
         
         this.pipelines.push(p);
@@ -446,11 +799,18 @@ impl RenderPipelineSystem{
         drop(this);
     }
 
-    pub unsafe fn render_shader(layer: u32, shader:Shader) {
+    pub unsafe fn render_shader(layer: u32, shader:Shader, data: Vec<Data>) {
         let p_this = Env::get_render_sys();
         let this = p_this.write();
         let pipelines = this.pipelines.clone();
         drop(this);
+        // the pipelines pick these up on the render thread
+        for p in &pipelines {
+            let mut pipeline = p.lock();
+            if pipeline.layer == layer {
+                pipeline.draws.push((shader.clone(), data.clone()));
+            }
+        }
     }
 
     pub unsafe fn register_camera(&mut self, layer: u32) -> i32 {
@@ -487,6 +847,29 @@ impl RenderPipelineSystem{
         return i;
     }
 
+    // replaces the stage with the same name (e.g. when a shader is hot reloaded), or registers it if there isn't one yet
+    pub unsafe fn update_shader_stage(shader_stage: ShaderStage) -> usize {
+        let mut p_rend = Env::get_render_sys();
+        let mut rend = p_rend.write();
+        if let Some(i) = rend.shader_stages_data.iter().position(|s| {s.stage_name.eq(&shader_stage.stage_name)}) {
+            rend.shader_stages_data[i] = shader_stage;
+            return i;
+        }
+        rend.shader_stages_data.push(shader_stage);
+        rend.shader_stages_data.len() - 1
+    }
+
+    // forgets the shader programs made from a shader so that they get rebuilt from the current stages
+    // TODO: delete the old programs on the render thread, they are leaked for now!!
+    pub unsafe fn invalidate_shader_program(asset_path: String) {
+        let p_this = Env::get_render_sys();
+        let this = p_this.read();
+        for p in &this.pipelines {
+            let mut pipeline = p.lock();
+            pipeline.shaders.remove(&asset_path);
+        }
+    }
+
     // pub unsafe fn update_shader(shader_data: ShaderData, shader_ptr: usize) {
     //     let mut p_rend = Env::get_render_sys();
     //     let mut rend = p_rend.write();
@@ -539,22 +922,44 @@ impl RenderPipelineSystem{
         }
     }
 
-    pub fn register_image(&mut self, image_data: &Vec<[u32; 4]> , width: u32, height: u32, depth: u32, image_name:String) -> Arc<Mutex<Image>> {
+    pub fn register_image(&mut self, image_data: &Vec<u8>, format: TextureFormat, width: u32, height: u32, image_name:String) -> Arc<Mutex<Image>> {
         let result = self.registered_images.get(&image_name);
         if let Some(value) = result {
             return value.clone();
         }
 
         // we didn't find anything, so we must register it!!
-        let image = Arc::new(Mutex::new(Image::new(image_data.clone(), width, height, false)));
-        self.registered_images.insert(image_name, image.clone());
+        self.insert_image(Image::new(image_data.clone(), format, width, height, false), image_name)
+    }
+
+    // registers an image that has already been made, e.g. a block compressed one. Like register_image, this gives
+    // back the one that is already registered if there is one!!
+    pub fn insert_image(&mut self, image: Image, image_name: String) -> Arc<Mutex<Image>> {
+        if let Some(value) = self.registered_images.get(&image_name) {
+            return value.clone();
+        }
+        let image = Arc::new(Mutex::new(image));
+        self.registered_images.insert(image_name.clone(), image.clone());
+        self.queue_image_upload(image_name);
         image
     }
 
+    // the image will be (re)uploaded to the gpu on the next frame, e.g. after its data has been replaced
+    pub fn queue_image_upload(&mut self, image_name: String) {
+        if !self.pending_images.contains(&image_name) {
+            self.pending_images.push(image_name);
+        }
+    }
+
     pub fn find_image(&self, image_name: String) -> Result<Arc<Mutex<Image>>, std::io::ErrorKind> {
         self.registered_images.get(&image_name).cloned().ok_or(std::io::ErrorKind::NotFound)
     }
 
+    // the name that an image was registered with, e.g. to find the gpu texture of a Texture
+    pub fn find_image_name(&self, image: &Arc<Mutex<Image>>) -> Option<String> {
+        self.registered_images.iter().find(|(_, i)| {Arc::ptr_eq(i, image)}).map(|(name, _)| {name.clone()})
+    }
+
     pub fn register_shader_data(shader_name: String, asset_path: String, data: Vec<u8>) {
         unsafe {
             let p_render_sys = Env::get_render_sys();
@@ -580,6 +985,7 @@ impl RenderPipelineSystem{
             sdl: sdl,
             shader_stages_data: vec![],
             registered_images: HashMap::<String, Arc<Mutex<Image>>>::new(),
+            pending_images: vec![],
             registered_shaders: HashMap::new(),
             shader_programs: HashMap::new(),
             input_data: HashMap::new(),
@@ -590,12 +996,24 @@ impl RenderPipelineSystem{
     pub fn processing<'a>(p_this: Arc<RwLock<Self>>) -> i32{
         unsafe{
            
-            let this = p_this.read();
+            let mut this = p_this.write();
             let p_recv = this.thread_reciever.clone();
             let p_pipelines = this.pipelines.clone();
             let p_window = this.window.clone();
             let p_video = this.video.clone();
+            let pending_images: Vec<(String, Arc<Mutex<Image>>)> = std::mem::take(&mut this.pending_images).into_iter()
+                .filter_map(|name| {this.registered_images.get(&name).cloned().map(|image| {(name, image)})}).collect();
+            let p_driver = this.driver_vals.clone();
             drop(this);
+
+            // images are uploaded here as this is the thread with the context
+            #[cfg(feature = "opengl")]
+            if !pending_images.is_empty() {
+                let mut driver = p_driver.lock();
+                for (name, image) in pending_images {
+                    DriverValues::upload_image(driver.as_mut().unwrap(), name, &image.lock());
+                }
+            }
             for p in p_pipelines{
                 let pipe = p.lock();
                 let is_init = pipe.is_init.clone();
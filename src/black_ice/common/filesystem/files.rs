@@ -6,11 +6,10 @@ use shaderc::CompileOptions;
 use std::any::TypeId;
 
 use crate::black_ice::common::engine::asset_types::shader_asset;
-use crate::black_ice::common::{APP_DIR, engine::asset_types::materials};
+use crate::black_ice::common::engine::asset_types::materials;
+use crate::black_ice::common::engine::config::EngineConfig;
 use crate::black_ice::common::engine::gamesys::*;
 
-#[cfg(target_os = "windows")] const ASSET_PATH: &str =  "F:\\Rust\\Program 1\\assets";
-#[cfg(target_os = "linux")] const ASSET_PATH: &str = "/home/detrix/rust/black-ice/assets";
 #[cfg(not(debug_assertions))] const IS_DEBUG: bool = false;
 #[cfg(debug_assertions)] const IS_DEBUG: bool = true;
 
@@ -47,6 +46,10 @@ pub enum MFType{
     FBX,
     OBJ,
     MTL,
+    GLTF,
+    GLB,
+    STL,
+    PLY,
     PNG,
     SHADER,
     UNKNOWN
@@ -61,6 +64,43 @@ trait Handlers {
     fn png_handler(&mut self) -> String;
 }
 
+impl MFType {
+
+    // the type of a mesh (or other) file from its ext, which can be given with or without the dot
+    pub fn from_ext(ext: &str) -> MFType {
+        let mut mf = MFType::NOEXT;
+        
+        if ext.eq("fbx") || ext.eq(".fbx") {
+            mf = MFType::FBX;
+        }
+        if ext.eq("png") || ext.eq(".png") {
+            mf = MFType::PNG;
+        }
+        if ext.eq(".obj") || ext.eq("obj") {
+            mf = MFType::OBJ;
+        }
+        if ext.eq("mtl") || ext.eq(".mtl") {
+            mf = MFType::MTL;
+        }
+        if ext.eq("gltf") || ext.eq(".gltf") {
+            mf = MFType::GLTF;
+        }
+        if ext.eq("glb") || ext.eq(".glb") {
+            mf = MFType::GLB;
+        }
+        if ext.eq("stl") || ext.eq(".stl") {
+            mf = MFType::STL;
+        }
+        if ext.eq("ply") || ext.eq(".ply") {
+            mf = MFType::PLY;
+        }
+        if ext.eq(".shad") || ext.eq("shad") || ext.eq(".glsl") || ext.eq("glsl") || ext.eq(".comp") || ext.eq("comp") || ext.eq(".frag") || ext.eq("frag") || ext.eq(".vert") || ext.eq("vert") {
+            mf = MFType::SHADER;
+        }
+        return mf;
+    }
+}
+
 pub trait Reader<U> {
     fn read(&mut self) -> U;
     fn read_file(&mut self) -> U;
@@ -78,7 +118,7 @@ impl FileSys{
 
         if IS_DEBUG
         {
-            let mut full_path = format!("{}\\{}", ASSET_PATH, _path[7..].to_owned());
+            let mut full_path = format!("{}\\{}", EngineConfig::get().asset_dir().display(), _path[7..].to_owned());
             full_path = String::from(full_path).replace("\\", "/");
             let dir = fs::metadata(full_path.clone()).unwrap();
 
@@ -94,6 +134,31 @@ impl FileSys{
         }
     }
 
+    // whether open() would find a file at this path, so that callers can skip files that aren't there instead of panicking
+    pub fn exists(_path: &str) -> bool {
+        if IS_DEBUG
+        {
+            match _path.get(7..) {
+                Some(rest) => {
+                    let full_path = format!("{}\\{}", EngineConfig::get().asset_dir().display(), rest).replace("\\", "/");
+                    fs::metadata(full_path).map(|m| {m.is_file()}).unwrap_or(false)
+                },
+                None => false
+            }
+        }
+        else
+        {
+            false
+        }
+    }
+
+    // reads the whole file as is, for binary files that can't go through read()
+    pub fn read_bytes(&mut self) -> Vec<u8> {
+        let mut result = Vec::new();
+        self.b.as_mut().expect("Failed to read file!!").read_to_end(&mut result).expect("Couldn't read anything!!");
+        result
+    }
+
     pub fn get_file_ext(&self) -> String {
         let mut last_i = 0;
 
@@ -133,24 +198,7 @@ impl FileSys{
         }
 
         let ext = self.path.get(last_i+1..).unwrap();
-        let mut mf = MFType::NOEXT;
-        
-        if ext.eq("fbx") || ext.eq(".fbx") {
-            mf = MFType::FBX;
-        }
-        if ext.eq("png") || ext.eq(".png") {
-            mf = MFType::PNG;
-        }
-        if ext.eq(".obj") || ext.eq("obj") {
-            mf = MFType::OBJ;
-        }
-        if ext.eq("mtl") || ext.eq(".mtl") {
-            mf = MFType::MTL;
-        }
-        if ext.eq(".shad") || ext.eq("shad") || ext.eq(".glsl") || ext.eq("glsl") || ext.eq(".comp") || ext.eq("comp") || ext.eq(".frag") || ext.eq("frag") || ext.eq(".vert") || ext.eq("vert") {
-            mf = MFType::SHADER;
-        }
-        return mf;
+        MFType::from_ext(ext)
 
     }
 
@@ -251,7 +299,7 @@ impl AsShaderFile for FileSys {
     }
 
     fn include_shaders() -> glsl_include::Context<'static> {
-        let path: String = APP_DIR.to_owned() + "\\assets\\shaders\\";
+        let path = EngineConfig::get().asset_dir().join("shaders");
         let mut directory = fs::read_dir(path).unwrap();
         let mut context: glsl_include::Context = glsl_include::Context::new();
         let mut path_stack = Vec::<ReadDir>::new();
@@ -350,7 +398,11 @@ impl Handlers for FileSys{
         unimplemented!();
     }
     fn mtl_handler(&mut self) -> String {
-        unimplemented!()
+        let buff = self.b.as_mut().unwrap();
+        let mut result = String::from("");
+
+        (*buff).read_to_string(&mut result).expect("Couldn't read anything!!");
+        return result;
     }
     fn obj_handler(&mut self) -> String {
         let buff = self.b.as_mut().unwrap();
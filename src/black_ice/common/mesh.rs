@@ -1,11 +1,11 @@
-use std::borrow::BorrowMut;
 use std::any::Any;
 use std::collections::HashMap;
 use std::sync::atomic::AtomicU32;
 use std::sync::Arc;
 use engine::asset_mgr::AssetManager;
+use engine::asset_types::{mesh_asset::MeshAsset, texture::Texture};
 use parking_lot::*;
-use crate::black_ice::common::{vertex::*, transform::*, engine::gamesys::*};
+use crate::black_ice::common::{vertex::*, transform::*, mesh_processing::*, engine::gamesys::*};
 use crate::black_ice::common::filesystem::files::*;
 use crate::black_ice::common::{engine::asset_types::materials::*, *};
 
@@ -21,15 +21,128 @@ pub enum SurfaceType {
     POINTS
 }
 
+/** The index buffer of a surface. Most surfaces are small enough for u16 indices, which halves the size of the buffer,
+* so it starts as u16 and switches over to u32 as soon as a vert past 65535 is used
+*/
+#[derive(Clone)]
+pub enum IndexBuffer {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl IndexBuffer {
+
+    pub fn new() -> Self {
+        IndexBuffer::U16(Vec::new())
+    }
+
+    pub fn push(&mut self, index: u32) {
+        if let IndexBuffer::U16(indices) = self {
+            if index <= u16::MAX as u32 {
+                indices.push(index as u16);
+                return;
+            }
+            *self = IndexBuffer::U32(indices.iter().map(|i| {*i as u32}).collect());
+        }
+        if let IndexBuffer::U32(indices) = self {
+            indices.push(index);
+        }
+    }
+
+    pub fn get(&self, i: usize) -> u32 {
+        match self {
+            IndexBuffer::U16(indices) => indices[i] as u32,
+            IndexBuffer::U32(indices) => indices[i],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            IndexBuffer::U16(indices) => indices.len(),
+            IndexBuffer::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = IndexBuffer::new();
+    }
+
+    pub fn is_u32(&self) -> bool {
+        matches!(self, IndexBuffer::U32(_))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.len()).map(|i| {self.get(i)})
+    }
+
+    pub fn to_u32(&self) -> Vec<u32> {
+        self.iter().collect()
+    }
+
+    // the buffer as it is uploaded to the GPU
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            IndexBuffer::U16(indices) => bytemuck::cast_slice(indices.as_slice()),
+            IndexBuffer::U32(indices) => bytemuck::cast_slice(indices.as_slice()),
+        }
+    }
+}
+
+// The attributes that a vert can have, in the order they are interleaved in the vertex buffer
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum VertexAttribute {
+    Position,
+    Normal,
+    Tangent,
+    UV0,
+    UV1,
+    Colour,
+}
+
+impl VertexAttribute {
+
+    // the location of the attribute in the shaders
+    pub fn location(&self) -> u32 {
+        match self {
+            VertexAttribute::Position => 0,
+            VertexAttribute::Normal => 1,
+            VertexAttribute::UV0 => 2,
+            VertexAttribute::Tangent => 3,
+            VertexAttribute::UV1 => 4,
+            VertexAttribute::Colour => 5,
+        }
+    }
+
+    // the number of floats it takes up
+    pub fn size(&self) -> usize {
+        match self {
+            VertexAttribute::Position | VertexAttribute::Normal => 3,
+            VertexAttribute::UV0 | VertexAttribute::UV1 => 2,
+            VertexAttribute::Tangent | VertexAttribute::Colour => 4,
+        }
+    }
+}
+
+/** A part of a mesh with a single material. Every attribute stream has one entry for each vert (so normals[i] is the
+* normal of verts[i]), apart from the optional ones which are left empty when the surface doesn't have them.
+*/
 pub struct Surface {
     pub id: u32,
     pub name: String,
     pub verts: Vec<Vec3>,
-    pub indices: Vec<i16>, //made of 3 verts
-    pub normals: Vec<(i16, Vec3)>,
-    pub texture_coord: Vec<(i16, (f32, f32))>,
+    pub indices: IndexBuffer, //made of 3 verts
+    pub normals: Vec<Vec3>,
+    pub tangents: Vec<[f32; 4]>,// optional, w is which way the bitangent goes (1 or -1)
+    pub texture_coord: Vec<(f32, f32)>,
+    pub texture_coord1: Vec<(f32, f32)>,// optional second uv set, for lightmaps and the like
+    pub colours: Vec<[f32; 4]>,// optional
     pub is_concave: bool,
-    pub surface_type: SurfaceType
+    pub surface_type: SurfaceType,
+    topology: Mutex<Option<Arc<mesh_topology::Topology>>>,// made the first time it's asked for
 }
 
 pub struct Mesh {
@@ -47,14 +160,13 @@ impl Mesh {
         mesh_object.add_point(Vec3::new(25.0, 25.0, 0.0));
         mesh_object.add_point(Vec3::new(50.0, 0.0, 0.0));
 
-        mesh_object.add_face(0, 1, 2);
-        mesh_object.add_normal(0, Vec3::new(0.0, 0.0, 1.0));
-        mesh_object.add_normal(1, Vec3::new(0.0, 0.0, 1.0));
-        mesh_object.add_normal(2, Vec3::new(0.0, 0.0, 1.0));
+        // anticlockwise, so that the generated normal faces +Z
+        mesh_object.add_face(0, 2, 1);
 
         mesh_object.add_uv(0, (0.0, 0.0));
         mesh_object.add_uv(1, (0.5, 0.5));
         mesh_object.add_uv(2, (1.0, 0.0));
+        mesh_object.generate_normals(true);
         let id = self.counter.fetch_add(1, std::sync::atomic::Ordering::Acquire);
         mesh_object.id = id.clone();
         self.surfaces.push(Arc::new(Mutex::new(mesh_object)));
@@ -70,19 +182,16 @@ impl Mesh {
         mesh_object.add_point(Vec3::new(v, v, 0.0));
 
         mesh_object.add_face(0, 1, 2);
-        mesh_object.add_normal(0, Vec3::new(0.0, 0.0, 1.0));
-        mesh_object.add_normal(1, Vec3::new(0.0, 0.0, 1.0));
-        mesh_object.add_normal(2, Vec3::new(0.0, 0.0, 1.0));
 
         mesh_object.add_point(Vec3::new(-v, v, 0.0));
 
         mesh_object.add_face(2, 3, 0);
-        mesh_object.add_normal(3, Vec3::new(0.0, 0.0, 1.0));
 
         mesh_object.add_uv(0, (0.0, 0.0));
         mesh_object.add_uv(1, (1.0, 0.0));
         mesh_object.add_uv(2, (1.0, 1.0));
         mesh_object.add_uv(3, (0.0, 1.0));
+        mesh_object.generate_normals(true);
         let id = self.counter.fetch_add(1, std::sync::atomic::Ordering::Acquire);
         mesh_object.id = id.clone();
         self.surfaces.push(Arc::new(Mutex::new(mesh_object)));
@@ -99,6 +208,7 @@ pub struct MeshFile {
     surfaces: Vec<Arc<Mutex<Surface>>>,
     pub mesh_file: FileSys,
     pub mesh_file_type: MFType,
+    pub path: String,// the FileSys or ASSET: path that the mesh was loaded from
     pub use_custom_materials: bool,
     pub materials: HashMap<u32, Arc<Mutex<Material>>>,
 }
@@ -107,20 +217,25 @@ pub trait MeshInstanciate<T> {
     fn new() -> T;
 }
 
+/** Questions about the shape of a mesh, for things like decals, navmesh baking and picking. See mesh_topology for
+* how the verts, edges and faces are numbered
+*/
 pub trait MeshRender {
-    fn render(&self, t: Transform);
-    fn get_vert(&self, i: i16) -> Vec3;
+    fn get_vert(&self, i: u32) -> Vec3;
     fn get_verts(&self) -> Vec<Vec3>;
-    fn get_edges(&self) -> Vec<i16>;
-    fn get_edge(&self, i: i16) -> (i16, i16);
-    fn get_edges_of_vert(&self, i: i16) -> Vec<(i16, i16)>;
-    fn get_face(&self, i: i16) -> (i16, i16, i16);
-    fn get_faces(&self, i: i16) -> Vec<i16>;
-    fn get_verts_of_face(&self, i: i16) -> Vec<i16>;
-
-    fn translated_verts(&self, t: Transform) -> Vec<Vec3>;
-    fn translated_faces(&self, t: Transform) -> Vec<(i16, i16, i16)>;
-    fn translated_edges(&self, t: Transform) -> Vec<(i16, i16, i16)>;
+    fn get_edges(&self) -> Vec<(u32, u32)>;
+    fn get_edge(&self, i: u32) -> (u32, u32);
+    fn get_edges_of_vert(&self, i: u32) -> Vec<(u32, u32)>;
+    fn get_face(&self, i: u32) -> (u32, u32, u32);
+    fn get_faces(&self, i: u32) -> Vec<u32>;// the faces that use vert i
+    fn get_verts_of_face(&self, i: u32) -> Vec<u32>;
+    fn get_neighbouring_faces(&self, i: u32) -> Vec<u32>;
+    fn get_edge_loop(&self, i: u32) -> Vec<u32>;
+
+    // the same as above, but moved into world space by the transform
+    fn translated_verts(&self, t: &Transform) -> Vec<Vec3>;
+    fn translated_faces(&self, t: &Transform) -> Vec<(Vec3, Vec3, Vec3)>;
+    fn translated_edges(&self, t: &Transform) -> Vec<(Vec3, Vec3)>;
 
 }
 
@@ -128,19 +243,23 @@ pub trait MeshFileSys {
     fn open(&mut self, f: &str);
     fn open_fbx(&mut self);
     fn open_obj(&mut self);
+    fn open_gltf(&mut self);
+    fn open_stl(&mut self);
+    fn open_ply(&mut self);
 }
 
 pub trait MeshConstruct {
     fn add_points(&mut self, verts: Vec<Vec3>);
-    fn add_point(&mut self, vert: Vec3) -> i16;
-    fn add_face(&mut self, vert1: i16, vert2: i16, vert3: i16);
-    fn add_edge(&mut self, vert1: i16, vert2: i16);
-    fn add_normal(&mut self, index: i16, normal: Vec3);
-    fn add_uv(&mut self, index: i16, coord: (f32, f32));
+    fn add_point(&mut self, vert: Vec3) -> u32;
+    fn add_face(&mut self, vert1: u32, vert2: u32, vert3: u32);
+    fn add_edge(&mut self, vert1: u32, vert2: u32);
+    fn add_normal(&mut self, index: u32, normal: Vec3);
+    fn add_uv(&mut self, index: u32, coord: (f32, f32));
 }
 
 //region Mesh Reflection
 impl Base for Surface{}
+impl Base for IndexBuffer{}
 impl Base for MeshFile{}
 
 impl Reflection for Surface{
@@ -161,7 +280,7 @@ impl Reflection for Surface{
             ref_type: self.verts.type_id()});
         register.addProp(Property { 
             name: Box::new("normals"), 
-            desc: Box::new("The normals of each face. This is ordered in the order of the faces. e.g. faces[1] has normal normals[1], faces[n] has normal normal[n]"), 
+            desc: Box::new("The normals of each vert. This is ordered in the order of the verts. e.g. verts[1] has normal normals[1], verts[n] has normal normals[n]"), 
             reference: Box::new(&self.normals), 
             ref_type: self.normals.type_id()});
 
@@ -185,7 +304,7 @@ impl Reflection for MeshFile {
 
 impl MeshInstanciate<MeshFile> for MeshFile {
     fn new() -> MeshFile {
-        return MeshFile {id_counter: AtomicU32::new(0), surfaces: Vec::<Arc<Mutex<Surface>>>::new(), mesh_file: FileSys::new(), mesh_file_type: MFType::UNKNOWN, use_custom_materials: false, materials: HashMap::new()};
+        return MeshFile {id_counter: AtomicU32::new(0), surfaces: Vec::<Arc<Mutex<Surface>>>::new(), mesh_file: FileSys::new(), mesh_file_type: MFType::UNKNOWN, path: String::new(), use_custom_materials: false, materials: HashMap::new()};
     }
 }
 
@@ -198,6 +317,9 @@ impl MeshFileSys for MeshFile {
         match self.mesh_file_type {
             MFType::FBX=>self.open_fbx(),
             MFType::OBJ=>self.open_obj(),
+            MFType::GLTF | MFType::GLB=>self.open_gltf(),
+            MFType::STL=>self.open_stl(),
+            MFType::PLY=>self.open_ply(),
             _=>{
 
             }
@@ -208,143 +330,192 @@ impl MeshFileSys for MeshFile {
 
     }
     fn open_obj(&mut self){
-        let buffer = self.mesh_file.read();
-        let mut _line_count = 0;
-        let mut _material_files: Vec<FileSys> = Vec::<FileSys>::new();
-        let mut texture_coords: Vec<(f32, f32)> = Vec::<(f32,f32)>::new();
-        let mut normals: Vec<Vec3> = Vec::<Vec3>::new();
-        //let mut vertices: Vec<Vec3> = Vec::<Vec3>::new();
-        let mut current_object = 0;
-        
-        
-
-        for i in 1..(get_number_of_lines(&buffer) - 1){
-
-            let line = get_line(&buffer, i);
-            let (line_type, split) = check_line(line);
-            let p_current = self.surfaces[current_object].clone();
-            let mut obj = p_current.lock();
-            match line_type{
-                Lntp::VERTEX =>         {
-                    let vertex = Vec3::new(split[1].parse::<f32>().unwrap(), split[2].parse::<f32>().unwrap(), split[3].parse::<f32>().unwrap());
-                    obj.add_point(vertex);
-                },
-                Lntp::VERTEX_TEXTURE => {texture_coords.push((split[1].parse::<f32>().unwrap(), split[2].parse::<f32>().unwrap()));},
-                Lntp::VERTEX_NORMAL => {normals.push(Vec3::new(split[1].parse::<f32>().unwrap(), split[2].parse::<f32>().unwrap(), split[3].parse::<f32>().unwrap()))},
-                Lntp::FACE => {
-                    let face_vertices = &split[1..];
-                    if face_vertices.len() > 3 {
-                        // Dealing with a non triangular face
+        self.import_file(MFType::OBJ);
+    }
+    fn open_gltf(&mut self){
+        let file_type = self.mesh_file.check_file_ext();
+        self.import_file(file_type);
+    }
+    fn open_stl(&mut self){
+        self.import_file(MFType::STL);
+    }
+    fn open_ply(&mut self){
+        self.import_file(MFType::PLY);
+    }
 
-                    }
-                    else{
-                        let mut a = face_vertices[0].split('/');
-                        let mut b = face_vertices[1].split('/');
-                        let mut c = face_vertices[2].split('/');
-                        obj.add_face(a.nth(0).unwrap().parse::<i16>().unwrap(), b.nth(0).unwrap().parse::<i16>().unwrap(), c.nth(0).unwrap().parse::<i16>().unwrap());
-                    }
-                },
-                Lntp::MTLLIB => {},
-                Lntp::OBJECT_NAME => {
-                    for i in 0..(normals.len()) {
-                        obj.add_normal(i.try_into().unwrap(), normals[i]);
-                        obj.add_uv(i.try_into().unwrap(), texture_coords[i]);
-                    }
-                    let id = self.id_counter.fetch_add(0, std::sync::atomic::Ordering::Acquire);
-                    self.surfaces.push(components::component_system::ComponentRef_new(
-                        Surface { 
-                            id: id.clone(),
-                            name: String::from(split[1]), 
-                            verts: Vec::new(), 
-                            indices: Vec::new(), 
-                            normals: Vec::new(), 
-                            texture_coord: Vec::new(), 
-                            is_concave: false,
-                            surface_type: SurfaceType::TRIANGLES
-                        }
-                    ));
-                    self.materials.insert(id, Arc::new(Mutex::new(Material::new())));
-                    current_object += 1;
+}
 
-                },
-                Lntp::USE_MTL => {},
-                Lntp::NONE => {
+impl MeshFile {
+    pub fn construct(definition: ConstructorDefinition) -> Self {
+        let mesh_file_path = definition.get("mesh_file_path").unwrap().as_str().expect("Failed to get Mesh File Path");
+        Self::from_path(mesh_file_path)
+    }
 
+    // ASSET:mount/path/to/mesh goes through the asset manager, the old ASSET:\\path\\to\\mesh paths are read straight
+    // from the asset folder
+    pub fn from_path(mesh_file_path: String) -> Self {
+        if Self::is_asset_path(&mesh_file_path) {
+            // a broken path in a scene file just gives an empty mesh, rather than taking the whole game down
+            let loaded = match AssetManager::try_load_asset::<MeshAsset>(mesh_file_path.clone()) {
+                Ok(asset) => asset.into_mesh_file().or_else(|| {
+                    println!("Mesh asset {} has no mesh file!!", mesh_file_path);
+                    None
+                }),
+                Err(e) => {
+                    println!("Failed to load mesh {}!! {}", mesh_file_path, e);
+                    None
                 }
-            }
-
-        }
-
-
-        #[allow(non_camel_case_types)]
-        enum Lntp {
-            VERTEX,
-            VERTEX_TEXTURE,
-            VERTEX_NORMAL,
-            FACE,
-            MTLLIB,
-            OBJECT_NAME,
-            USE_MTL,
-            NONE
+            };
+            return loaded.unwrap_or_else(|| {
+                let mut mesh_file = Self::new();
+                mesh_file.path = mesh_file_path;
+                mesh_file
+            });
         }
+        let mut mesh_file = Self::new();
+        mesh_file.open(mesh_file_path.as_str());
+        mesh_file
+    }
 
-        fn get_number_of_lines(buffer: &str) -> usize{
-            return buffer.lines().collect::<Vec<_>>().len();
+    pub fn is_asset_path(path: &String) -> bool {
+        match path.strip_prefix("ASSET:") {
+            Some(rest) => !rest.starts_with('\\') && !rest.starts_with('/'),
+            None => false
         }
-        fn get_line(buffer: &str, i: usize) -> &str{
+    }
 
-            let lines = buffer.lines().collect::<Vec<_>>();
-            if i > lines.len() {
-                return "";
-            }
-            return lines[i];
+    /** Reads a mesh out of the data of a mesh file. `load_file` is given the full path of any other file that the mesh
+    * refers to (like an OBJ's mtllib or a glTF's buffers) and should give back its data, or None if it can't be found.
+    * The paths are made relative to `path`, so when `path` is an ASSET: path they can be loaded from the asset manager.
+    */
+    pub fn import(&mut self, path: String, file_type: MFType, data: &[u8], load_file: &dyn Fn(&str) -> Option<Vec<u8>>) {
+        self.path = path;
+        self.mesh_file_type = file_type;
+        let (base_dir, name) = self.split_path();
+        let load_relative = |file: &str| {load_file(format!("{}/{}", base_dir, file).as_str())};
+
+        let imported = match file_type {
+            MFType::OBJ => Ok(mesh_import::obj::parse_obj(name, String::from_utf8_lossy(data).as_ref(), &|file: &str| {
+                load_relative(file).map(|mtl| {String::from_utf8_lossy(&mtl).to_string()})
+            })),
+            MFType::GLTF | MFType::GLB => mesh_import::gltf::parse_gltf(name, data, &load_relative),
+            MFType::STL => mesh_import::stl::parse_stl(name, data),
+            MFType::PLY => mesh_import::ply::parse_ply(name, data),
+            _ => Err("Meshes of this type can't be loaded!!".to_string())
+        };
+        match imported {
+            Ok(mesh) => self.add_imported(mesh, &base_dir, load_file),
+            Err(e) => println!("Failed to load mesh {}!! {}", self.path, e)
         }
+    }
 
-        fn check_line(line: &str) -> (Lntp, Vec<&str>)  {
-            let mut result = Lntp::NONE;
-            let split: Vec<_> = line.split_whitespace().collect();
-            
-            if split[0] == "v" {
-                result = Lntp::VERTEX;
-            }
-            if split[0] == "vt" {
-                result = Lntp::VERTEX_TEXTURE;
-            }
-            if split[0] == "vn" {
-                result = Lntp::VERTEX_NORMAL;
-            }
-            if split[0] == "f" {
-                result = Lntp::FACE;
+    // imports the file that was opened with FileSys
+    fn import_file(&mut self, file_type: MFType) {
+        let data = self.mesh_file.read_bytes();
+        self.import(self.mesh_file.path.clone(), file_type, data.as_slice(), &|file: &str| {
+            if !FileSys::exists(file) {
+                return None;
             }
-            if split[0] == "o" {
-                result = Lntp::OBJECT_NAME;
-            }
-            if split[0] == "usemtl" {
-                result = Lntp::USE_MTL;
-            }
-            if split[0] == "mtllib" {
-                result = Lntp::MTLLIB;
-            }
-
-            
+            let mut other_file = FileSys::new();
+            other_file.open(file);
+            Some(other_file.read_bytes())
+        });
+    }
 
-            return (result, split);
+    // the folder that the mesh file is in, and the name of the file
+    fn split_path(&self) -> (String, String) {
+        let path = &self.path;
+        match path.rfind(|c| {c == '/' || c == '\\'}) {
+            Some(i) => (path[..i].to_string(), path[i + 1..].to_string()),
+            None => (String::new(), path.clone())
         }
-
-
     }
 
+    // takes the surfaces that an importer read, giving each one an id and the material it asked for
+    fn add_imported(&mut self, imported: mesh_import::ImportedMesh, base_dir: &String, load_file: &dyn Fn(&str) -> Option<Vec<u8>>) {
+        // each material is only made once, and shared between the surfaces that use it
+        let mut materials: HashMap<String, Arc<Mutex<Material>>> = HashMap::new();
+        for surface in imported.surfaces {
+            let material_name = surface.material;
+            let mut surface = surface.surface;
+            let source = material_name.as_ref().and_then(|m| {imported.materials.get(m)});
+            Self::prepare_surface(&mut surface, source);
+            let id = self.id_counter.fetch_add(1, std::sync::atomic::Ordering::Acquire);
+            surface.id = id;
+            self.surfaces.push(Arc::new(Mutex::new(surface)));
+
+            let material = match source {
+                Some(source) => materials.entry(source.name.clone()).or_insert_with(|| {
+                    Arc::new(Mutex::new(Self::make_material(source, base_dir, &self.path, load_file)))
+                }).clone(),
+                None => Arc::new(Mutex::new(Material::new()))
+            };
+            self.materials.insert(id, material);
+        }
+    }
 
+    // fills in whatever the file left out that the renderer needs
+    fn prepare_surface(surface: &mut Surface, material: Option<&mesh_import::ImportedMaterial>) {
+        if !surface.has_normals() {
+            // only the verts that the file didn't give a normal get the generated one
+            let given = std::mem::take(&mut surface.normals);
+            surface.generate_normals(true);
+            for (normal, given) in surface.normals.iter_mut().zip(given) {
+                if given.magnitude() > f32::EPSILON {
+                    *normal = given;
+                }
+            }
+        }
+        let normal_mapped = material.map(|m| {m.textures.iter().any(|(sampler, _)| {sampler == "material.normal_map"})}).unwrap_or(false);
+        if normal_mapped && surface.tangents.is_empty() {
+            surface.generate_tangents();
+        }
+    }
 
-
-}
-
-impl MeshFile {
-    pub fn construct(definition: ConstructorDefinition) -> Self {
-        let mesh_file_path = definition.get("mesh_file_path").unwrap().as_str().expect("Failed to get Mesh File Path");
-        let mut mesh_file = Self::new();
-        mesh_file.open(mesh_file_path.as_str());
-        mesh_file
+    fn make_material(source: &mesh_import::ImportedMaterial, base_dir: &String, mesh_path: &String, load_file: &dyn Fn(&str) -> Option<Vec<u8>>) -> Material {
+        let mut material = Material::new();
+        for (param, value) in &source.params {
+            material.set_param(param.clone(), value.clone());
+        }
+        for (i, (sampler, texture)) in source.textures.iter().enumerate() {
+            match texture {
+                mesh_import::ImportedTexture::File(file) => {
+                    let texture_path = format!("{}/{}", base_dir, file);
+                    if !Self::is_asset_path(mesh_path) {
+                        // meshes opened with FileSys (like OBJs with an MTL) can't use the asset manager, so the texture
+                        // is read in the same way as the mesh's other files
+                        match load_file(texture_path.as_str()) {
+                            Some(data) => {
+                                let ext = file.rsplit_once('.').map(|(_, ext)| {ext.to_lowercase()}).unwrap_or_default();
+                                let metadata = HashMap::from([("ext".to_string(), ext)]);
+                                match Texture::from_memory(texture_path, data.as_slice(), &metadata) {
+                                    Ok(texture) => material.set_texture(sampler.clone(), texture),
+                                    Err(e) => println!("Failed to load texture {} for material {}!! {}", file, source.name, e)
+                                }
+                            },
+                            None => println!("Failed to load texture {} for material {}!!", file, source.name)
+                        }
+                        continue;
+                    }
+                    // the mesh may not be the asset being initialised (e.g. a MeshFile reimporting), so the dependency is added here too
+                    AssetManager::add_dependency(mesh_path.clone(), texture_path.clone());
+                    match AssetManager::try_load_asset::<Texture>(texture_path) {
+                        Ok(texture) => material.set_texture(sampler.clone(), texture),
+                        Err(e) => println!("Failed to load texture {} for material {}!! {}", file, source.name, e)
+                    }
+                },
+                mesh_import::ImportedTexture::Embedded(data, ext) => {
+                    // embedded images are registered under the mesh file, so that they are shared like any other texture
+                    let metadata = HashMap::from([("ext".to_string(), ext.clone())]);
+                    let name = format!("{}#{}.{}", mesh_path, source.name, i);
+                    match Texture::from_memory(name, data.as_slice(), &metadata) {
+                        Ok(texture) => material.set_texture(sampler.clone(), texture),
+                        Err(e) => println!("Failed to load embedded texture {} for material {}!! {}", i, source.name, e)
+                    }
+                }
+            }
+        }
+        material
     }
 
     pub fn as_mesh(&self) -> Mesh {
@@ -356,50 +527,109 @@ impl MeshConstruct for Surface {
 
     /// This gives a list of points that are in a mesh
     fn add_points(&mut self, verts: Vec<Vec3>) {
-        let &mut length = self.verts.len().borrow_mut();
-        self.verts.append(&mut verts.clone());
-        for i in 0..(&verts.len() - 1){
-            self.normals.push(((length + i).try_into().unwrap(), Vec3::new(0, 0, 0)));
-            self.texture_coord.push(((length + i).try_into().unwrap(), (0.0, 0.0)));
+        for vert in verts {
+            self.add_point(vert);
         }
     }
-    fn add_face(&mut self, vert1: i16, vert2: i16, vert3: i16) {
+    fn add_face(&mut self, vert1: u32, vert2: u32, vert3: u32) {
         assert!(self.surface_type == SurfaceType::TRIANGLES);// Must be triangles for 
-        self.indices.append(&mut vec![vert1, vert2, vert3]);
+        self.indices.push(vert1);
+        self.indices.push(vert2);
+        self.indices.push(vert3);
     }
 
-    fn add_edge(&mut self, vert1: i16, vert2: i16){
+    fn add_edge(&mut self, vert1: u32, vert2: u32){
 
     }
 
-    fn add_normal(&mut self, index: i16, normal: Vec3) {
-        for norm in &mut self.normals{
-            if norm.0 == index {
-                norm.1 = normal.clone();
-                return;
-            }
-        }
+    fn add_normal(&mut self, index: u32, normal: Vec3) {
+        self.normals[index as usize] = normal;
     }
 
-    fn add_point(&mut self, vert: Vec3) -> i16 {
+    fn add_point(&mut self, vert: Vec3) -> u32 {
         self.verts.push(vert);
-        self.normals.push(((self.verts.len() - 1).try_into().unwrap(), Vec3::new(0, 0, 0)));
-        self.texture_coord.push(((self.verts.len() - 1).try_into().unwrap(), (0.0, 0.0)));
-        return (self.verts.len() - 1).try_into().unwrap();
+        self.normals.push(Vec3::new(0, 0, 0));
+        self.texture_coord.push((0.0, 0.0));
+        // the optional streams only need filling in once they are being used
+        if !self.tangents.is_empty() {
+            self.tangents.push([1.0, 0.0, 0.0, 1.0]);
+        }
+        if !self.texture_coord1.is_empty() {
+            self.texture_coord1.push((0.0, 0.0));
+        }
+        if !self.colours.is_empty() {
+            self.colours.push([1.0; 4]);
+        }
+        return (self.verts.len() - 1) as u32;
     }
 
-    fn add_uv(&mut self, index: i16, coord: (f32, f32)) {
-        for uv in &mut self.texture_coord {
-            if uv.0 == index {
-                uv.1 = coord;
-                return;
-            }
-        }
+    fn add_uv(&mut self, index: u32, coord: (f32, f32)) {
+        self.texture_coord[index as usize] = coord;
     }
 }
 
 impl Surface {
     pub fn new(name: String, surface_type: SurfaceType) -> Self {
-        Self { id: 0, name: name.clone(), verts: Vec::new(), indices: Vec::new(), normals: Vec::new(), texture_coord: Vec::new(), is_concave: false, surface_type: surface_type }
+        Self { id: 0, name: name.clone(), verts: Vec::new(), indices: IndexBuffer::new(), normals: Vec::new(), tangents: Vec::new(), texture_coord: Vec::new(), texture_coord1: Vec::new(), colours: Vec::new(), is_concave: false, surface_type: surface_type, topology: Mutex::new(None) }
+    }
+
+    /** The half edges of the surface, made when they are first needed. It is made again when verts or faces are added,
+    * but anything else that moves the verts or changes the indices should call clear_topology
+    */
+    pub fn topology(&self) -> Arc<mesh_topology::Topology> {
+        let mut topology = self.topology.lock();
+        match topology.as_ref() {
+            Some(t) if !t.is_out_of_date(self) => t.clone(),
+            _ => {
+                let t = Arc::new(mesh_topology::Topology::new(self));
+                *topology = Some(t.clone());
+                t
+            }
+        }
+    }
+
+    pub fn clear_topology(&self) {
+        *self.topology.lock() = None;
+    }
+
+    // the attributes that every vert of this surface has
+    pub fn vertex_layout(&self) -> Vec<VertexAttribute> {
+        let count = self.verts.len();
+        let mut layout = vec![VertexAttribute::Position];
+        if self.normals.len() == count {
+            layout.push(VertexAttribute::Normal);
+        }
+        if self.tangents.len() == count && count > 0 {
+            layout.push(VertexAttribute::Tangent);
+        }
+        if self.texture_coord.len() == count {
+            layout.push(VertexAttribute::UV0);
+        }
+        if self.texture_coord1.len() == count && count > 0 {
+            layout.push(VertexAttribute::UV1);
+        }
+        if self.colours.len() == count && count > 0 {
+            layout.push(VertexAttribute::Colour);
+        }
+        layout
+    }
+
+    // interleaves the attribute streams into one vertex buffer, in the order of the layout
+    pub fn vertex_data(&self, layout: &[VertexAttribute]) -> Vec<f32> {
+        let stride: usize = layout.iter().map(|a| {a.size()}).sum();
+        let mut data = Vec::with_capacity(stride * self.verts.len());
+        for i in 0..self.verts.len() {
+            for attribute in layout {
+                match attribute {
+                    VertexAttribute::Position => data.extend(self.verts[i].get()),
+                    VertexAttribute::Normal => data.extend(self.normals[i].get()),
+                    VertexAttribute::Tangent => data.extend(self.tangents[i]),
+                    VertexAttribute::UV0 => data.extend([self.texture_coord[i].0, self.texture_coord[i].1]),
+                    VertexAttribute::UV1 => data.extend([self.texture_coord1[i].0, self.texture_coord1[i].1]),
+                    VertexAttribute::Colour => data.extend(self.colours[i]),
+                }
+            }
+        }
+        data
     }
 }
\ No newline at end of file
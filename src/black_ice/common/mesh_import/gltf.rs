@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use ::gltf::{buffer, image, mesh::Mode, Gltf, Node};
+
+use crate::black_ice::common::{engine::asset_types::shader_asset::ShaderDataType, vertex::*};
+
+use super::*;
+
+type Matrix = [[f32; 4]; 4];// column major, the same as gltf gives them
+
+const IDENTITY: Matrix = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+/** Reads a glTF 2.0 file (either the .gltf json or a binary .glb). Every primitive of every mesh in the scene becomes a
+* surface, with the transforms of the nodes baked into the verts. `load_file` is given the uri of each external buffer or
+* image (relative to the glTF file) and should give back its data, or None if it can't be found.
+*/
+pub fn parse_gltf(name: String, data: &[u8], load_file: &dyn Fn(&str) -> Option<Vec<u8>>) -> Result<ImportedMesh, String> {
+    let gltf = Gltf::from_slice(data).map_err(|e| {e.to_string()})?;
+
+    let mut buffers: Vec<Vec<u8>> = vec![];
+    for buffer in gltf.buffers() {
+        let data = match buffer.source() {
+            buffer::Source::Bin => gltf.blob.clone().ok_or("GLB file has no binary chunk!!".to_string())?,
+            buffer::Source::Uri(uri) => read_uri(uri, load_file).ok_or(format!("Couldn't load buffer {}!!", uri))?
+        };
+        if data.len() < buffer.length() {
+            return Err(format!("Buffer {} is too short!!", buffer.index()));
+        }
+        buffers.push(data);
+    }
+
+    let mut builder: SurfaceBuilder<u32> = SurfaceBuilder::new(name.clone());
+    let mut materials: HashMap<String, ImportedMaterial> = HashMap::new();
+    let mut add_mesh = |node: &Node, transform: &Matrix| {
+        let mesh = match node.mesh() {
+            Some(m) => m,
+            None => return
+        };
+        let mesh_name = mesh.name().or(node.name()).map(|n| {n.to_string()}).unwrap_or(format!("{}{}", name, mesh.index()));
+        // a negative scale turns the triangles inside out, so they need to be flipped back
+        let flip = determinant(transform) < 0.0;
+        let normal_matrix = normal_matrix(transform);
+
+        for primitive in mesh.primitives() {
+            let reader = primitive.reader(|b| {buffers.get(b.index()).map(|d| {d.as_slice()})});
+            let positions: Vec<[f32; 3]> = match reader.read_positions() {
+                Some(p) => p.collect(),
+                None => continue
+            };
+            let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|n| {n.collect()});
+            let uvs: Option<Vec<[f32; 2]>> = reader.read_tex_coords(0).map(|t| {t.into_f32().collect()});
+            let uvs1: Option<Vec<[f32; 2]>> = reader.read_tex_coords(1).map(|t| {t.into_f32().collect()});
+            let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(|t| {t.collect()});
+            let colours: Option<Vec<[f32; 4]>> = reader.read_colors(0).map(|c| {c.into_rgba_f32().collect()});
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(i) => i.into_u32().collect(),
+                None => (0..positions.len() as u32).collect()
+            };
+            let triangles = match triangles(primitive.mode(), &indices) {
+                Some(t) => t,
+                None => {
+                    println!("Skipping {:?} primitive in {}, only triangles are supported!!", primitive.mode(), mesh_name);
+                    continue;
+                }
+            };
+
+            let material = primitive.material();
+            let material_name = material.index().map(|i| {material.name().map(|n| {n.to_string()}).unwrap_or(format!("material{}", i))});
+            if let Some(material_name) = material_name.as_ref() {
+                if !materials.contains_key(material_name) {
+                    materials.insert(material_name.clone(), read_material(&material, material_name.clone(), &buffers, load_file));
+                }
+            }
+
+            builder.start(format!("{}.{}", mesh_name, primitive.index()), material_name);
+            for triangle in triangles {
+                let mut corners = triangle.map(|i| {
+                    let i = i as usize;
+                    if i >= positions.len() {
+                        return None;
+                    }
+                    let position = transform_point(transform, positions[i]);
+                    let normal = normals.as_ref().and_then(|n| {n.get(i)}).map(|n| {transform_normal(&normal_matrix, *n)});
+                    let uv = uvs.as_ref().and_then(|t| {t.get(i)}).map(|t| {(t[0], t[1])});
+                    let index = builder.vertex(i as u32, position, normal, uv);
+                    if let Some(uv1) = uvs1.as_ref().and_then(|t| {t.get(i)}) {
+                        builder.uv1(index, (uv1[0], uv1[1]));
+                    }
+                    if let Some(tangent) = tangents.as_ref().and_then(|t| {t.get(i)}) {
+                        // tangents turn with the model, and the bitangent flips along with the triangles
+                        let turned = transform_direction(transform, [tangent[0], tangent[1], tangent[2]]);
+                        let handedness = if flip { -tangent[3] } else { tangent[3] };
+                        builder.tangent(index, [turned.x, turned.y, turned.z, handedness]);
+                    }
+                    if let Some(colour) = colours.as_ref().and_then(|c| {c.get(i)}) {
+                        builder.colour(index, *colour);
+                    }
+                    Some(index)
+                });
+                if flip {
+                    corners.swap(1, 2);
+                }
+                if let [Some(a), Some(b), Some(c)] = corners {
+                    builder.triangle(a, b, c);
+                }
+            }
+        }
+    };
+
+    // walk the scene from its root nodes so that every node gets its parents' transforms
+    let scene = gltf.default_scene().or(gltf.scenes().next());
+    let mut stack: Vec<(Node, Matrix)> = match scene {
+        Some(scene) => scene.nodes().map(|n| {(n, IDENTITY)}).collect(),
+        None => gltf.nodes().map(|n| {(n, IDENTITY)}).collect()// no scenes, so just show every node where it is
+    };
+    while let Some((node, parent)) = stack.pop() {
+        let transform = multiply(&parent, &node.transform().matrix());
+        add_mesh(&node, &transform);
+        for child in node.children() {
+            stack.push((child, transform));
+        }
+    }
+
+    Ok(ImportedMesh { surfaces: builder.finish(), materials })
+}
+
+// the triangles of a primitive, or None if it isn't made of triangles
+fn triangles(mode: Mode, indices: &[u32]) -> Option<Vec<[u32; 3]>> {
+    match mode {
+        Mode::Triangles => Some(indices.chunks_exact(3).map(|t| {[t[0], t[1], t[2]]}).collect()),
+        Mode::TriangleStrip => Some((2..indices.len()).map(|i| {
+            // every other triangle is the other way round
+            if i % 2 == 0 { [indices[i - 2], indices[i - 1], indices[i]] } else { [indices[i - 1], indices[i - 2], indices[i]] }
+        }).collect()),
+        Mode::TriangleFan => Some((2..indices.len()).map(|i| {[indices[0], indices[i - 1], indices[i]]}).collect()),
+        _ => None
+    }
+}
+
+fn read_material(material: &::gltf::Material, name: String, buffers: &[Vec<u8>], load_file: &dyn Fn(&str) -> Option<Vec<u8>>) -> ImportedMaterial {
+    let pbr = material.pbr_metallic_roughness();
+    let emissive = material.emissive_factor();
+    let params = vec![
+        ("material.base_colour".to_string(), ShaderDataType::Vec4(pbr.base_color_factor())),
+        ("material.metallic".to_string(), ShaderDataType::Float(pbr.metallic_factor())),
+        ("material.roughness".to_string(), ShaderDataType::Float(pbr.roughness_factor())),
+        ("material.emissive".to_string(), ShaderDataType::Vec3(emissive)),
+    ];
+
+    let mut textures = vec![];
+    if let Some(info) = pbr.base_color_texture() {
+        let image = info.texture().source();
+        let texture = match image.source() {
+            image::Source::View { view, mime_type } => {
+                let start = view.offset();
+                buffers.get(view.buffer().index())
+                    .and_then(|b| {b.get(start..start + view.length())})
+                    .map(|data| {ImportedTexture::Embedded(data.to_vec(), mime_ext(mime_type).to_string())})
+            },
+            image::Source::Uri { uri, mime_type } if uri.starts_with("data:") => {
+                let ext = mime_type.map(mime_ext).unwrap_or(mime_ext(&uri[5..uri.find(';').unwrap_or(5)]));
+                read_uri(uri, load_file).map(|data| {ImportedTexture::Embedded(data, ext.to_string())})
+            },
+            image::Source::Uri { uri, .. } => Some(ImportedTexture::File(percent_decode(uri)))
+        };
+        match texture {
+            Some(texture) => textures.push(("material.base_colour_map".to_string(), texture)),
+            None => println!("Couldn't read the base colour texture of material {}!!", name)
+        }
+    }
+    ImportedMaterial { name, params, textures }
+}
+
+fn mime_ext(mime_type: &str) -> &str {
+    match mime_type {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        _ => mime_type.rsplit('/').next().unwrap_or("")
+    }
+}
+
+// the data of a uri, which is either base64 data inside the uri or a file next to the glTF file
+fn read_uri(uri: &str, load_file: &dyn Fn(&str) -> Option<Vec<u8>>) -> Option<Vec<u8>> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (header, data) = data.split_once(',')?;
+        if !header.ends_with(";base64") {
+            return Some(percent_decode(data).into_bytes());
+        }
+        return decode_base64(data);
+    }
+    load_file(percent_decode(uri).as_str())
+}
+
+// uris can have %20 and so on in them
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut result = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|h| {u8::from_str_radix(h, 16).ok()});
+            if let Some(byte) = hex {
+                result.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        result.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&result).to_string()
+}
+
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+            b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+            b'+' | b'-' => Some(62),
+            b'/' | b'_' => Some(63),
+            _ => None
+        }
+    };
+    let mut result = Vec::with_capacity(data.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in data.bytes().filter(|c| {!c.is_ascii_whitespace() && *c != b'='}) {
+        bits = (bits << 6) | value(c)?;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            result.push((bits >> count) as u8);
+        }
+    }
+    Some(result)
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result = [[0.0; 4]; 4];
+    for col in 0..4 {
+        for row in 0..4 {
+            result[col][row] = (0..4).map(|k| {a[k][row] * b[col][k]}).sum();
+        }
+    }
+    result
+}
+
+fn determinant(m: &Matrix) -> f32 {
+    m[0][0] * (m[1][1] * m[2][2] - m[2][1] * m[1][2])
+        - m[1][0] * (m[0][1] * m[2][2] - m[2][1] * m[0][2])
+        + m[2][0] * (m[0][1] * m[1][2] - m[1][1] * m[0][2])
+}
+
+// the cofactors of the top 3x3 of the matrix, which is the inverse transpose without the divide. The normals get
+// normalized anyway, so the scale doesn't matter!!
+fn normal_matrix(m: &Matrix) -> [[f32; 3]; 3] {
+    let column = |i: usize| {[m[i][0], m[i][1], m[i][2]]};
+    let cross = |u: [f32; 3], v: [f32; 3]| {[u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]]};
+    let (x, y, z) = (column(0), column(1), column(2));
+    let sign = if determinant(m) < 0.0 { -1.0 } else { 1.0 };
+    [cross(y, z), cross(z, x), cross(x, y)].map(|col| {col.map(|v| {v * sign})})
+}
+
+fn transform_point(m: &Matrix, p: [f32; 3]) -> Vec3 {
+    let v = |row: usize| {m[0][row] * p[0] + m[1][row] * p[1] + m[2][row] * p[2] + m[3][row]};
+    Vec3::new(v(0), v(1), v(2))
+}
+
+fn transform_direction(m: &Matrix, d: [f32; 3]) -> Vec3 {
+    let v = |row: usize| {m[0][row] * d[0] + m[1][row] * d[1] + m[2][row] * d[2]};
+    Vec3::new(v(0), v(1), v(2)).normalized()
+}
+
+fn transform_normal(m: &[[f32; 3]; 3], n: [f32; 3]) -> Vec3 {
+    let v = |row: usize| {m[0][row] * n[0] + m[1][row] * n[1] + m[2][row] * n[2]};
+    Vec3::new(v(0), v(1), v(2)).normalized()
+}
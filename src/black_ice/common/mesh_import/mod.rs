@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::black_ice::common::{engine::asset_types::shader_asset::ShaderDataType, mesh::*, vertex::*};
+
+pub mod obj;
+pub mod gltf;
+pub mod stl;
+pub mod ply;
+
+/** A surface made by an importer, along with the name of the material it uses (if the file gave it one) */
+pub struct ImportedSurface {
+    pub surface: Surface,
+    pub material: Option<String>,
+}
+
+pub enum ImportedTexture {
+    File(String),// a path relative to the mesh file
+    Embedded(Vec<u8>, String),// the encoded image and its ext, for images stored inside the mesh file
+}
+
+/** The material parameters that an importer could find. The params are uniforms named the same as in the engine's
+* shaders (material.diffuse etc.), and the textures are given with the name of the sampler they are for
+*/
+pub struct ImportedMaterial {
+    pub name: String,
+    pub params: Vec<(String, ShaderDataType)>,
+    pub textures: Vec<(String, ImportedTexture)>,
+}
+
+/** Everything that an importer read out of a mesh file */
+pub struct ImportedMesh {
+    pub surfaces: Vec<ImportedSurface>,
+    pub materials: HashMap<String, ImportedMaterial>,
+}
+
+/** Builds surfaces out of file formats that index each attribute on its own (like OBJ's v/vt/vn). Every distinct
+* combination of attributes becomes one vertex of the surface, so that the normals and uvs line up with the verts.
+*/
+pub struct SurfaceBuilder<K> {
+    name: String,
+    material: Option<String>,
+    current: Surface,
+    corners: HashMap<K, u32>,
+    surfaces: Vec<ImportedSurface>,
+}
+
+impl<K> SurfaceBuilder<K> where K : Hash + Eq {
+
+    pub fn new(name: String) -> Self {
+        Self { name: name.clone(), material: None, current: Surface::new(name, SurfaceType::TRIANGLES), corners: HashMap::new(), surfaces: vec![] }
+    }
+
+    // starts a new surface, unless the current one hasn't got anything in it yet
+    pub fn start(&mut self, name: String, material: Option<String>) {
+        if self.current.verts.is_empty() {
+            self.current.name = name.clone();
+        }
+        else {
+            self.flush();
+            self.current = Surface::new(name.clone(), SurfaceType::TRIANGLES);
+        }
+        self.name = name;
+        self.material = material;
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    // gives back the vertex for a combination of attributes, adding it if it hasn't been seen before
+    pub fn vertex(&mut self, key: K, position: Vec3, normal: Option<Vec3>, uv: Option<(f32, f32)>) -> u32 {
+        if let Some(index) = self.corners.get(&key) {
+            return *index;
+        }
+        let index = self.current.add_point(position);
+        if let Some(normal) = normal {
+            self.current.add_normal(index, normal);
+        }
+        if let Some(uv) = uv {
+            self.current.add_uv(index, uv);
+        }
+        self.corners.insert(key, index);
+        index
+    }
+
+    // gives a vertex a colour. The verts that never get one are white
+    pub fn colour(&mut self, index: u32, colour: [f32; 4]) {
+        let len = self.current.verts.len();
+        if self.current.colours.len() < len {
+            self.current.colours.resize(len, [1.0; 4]);
+        }
+        self.current.colours[index as usize] = colour;
+    }
+
+    pub fn tangent(&mut self, index: u32, tangent: [f32; 4]) {
+        let len = self.current.verts.len();
+        if self.current.tangents.len() < len {
+            self.current.tangents.resize(len, [1.0, 0.0, 0.0, 1.0]);
+        }
+        self.current.tangents[index as usize] = tangent;
+    }
+
+    // the second set of uvs
+    pub fn uv1(&mut self, index: u32, uv: (f32, f32)) {
+        let len = self.current.verts.len();
+        if self.current.texture_coord1.len() < len {
+            self.current.texture_coord1.resize(len, (0.0, 0.0));
+        }
+        self.current.texture_coord1[index as usize] = uv;
+    }
+
+    pub fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        self.current.add_face(a, b, c);
+    }
+
+    fn flush(&mut self) {
+        let surface = std::mem::replace(&mut self.current, Surface::new(self.name.clone(), SurfaceType::TRIANGLES));
+        self.corners.clear();
+        if !surface.indices.is_empty() {
+            self.surfaces.push(ImportedSurface { surface, material: self.material.clone() });
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<ImportedSurface> {
+        self.flush();
+        self.surfaces
+    }
+}
+
+/** Splits a polygon into triangles by ear clipping, giving back the indices into `points` of each triangle. The polygon
+* can be concave but shouldn't cross itself. If no ear can be found (the polygon is broken) the rest is fanned!!
+*/
+pub fn triangulate(points: &[Vec3]) -> Vec<[usize; 3]> {
+    let count = points.len();
+    if count < 3 {
+        return vec![];
+    }
+    if count == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // project onto the plane that the polygon faces the most, found with newell's method
+    let mut normal = [0.0f32; 3];
+    for i in 0..count {
+        let (a, b) = (points[i], points[(i + 1) % count]);
+        normal[0] += (a.y - b.y) * (a.z + b.z);
+        normal[1] += (a.z - b.z) * (a.x + b.x);
+        normal[2] += (a.x - b.x) * (a.y + b.y);
+    }
+    let (ax, ay) = if normal[2].abs() >= normal[0].abs() && normal[2].abs() >= normal[1].abs() { (0, 1) }
+        else if normal[0].abs() >= normal[1].abs() { (1, 2) }
+        else { (2, 0) };
+    let flat: Vec<[f32; 2]> = points.iter().map(|p| {let p = [p.x, p.y, p.z]; [p[ax], p[ay]]}).collect();
+
+    // which way round the polygon goes once it is flat
+    let area: f32 = (0..count).map(|i| {
+        let (a, b) = (flat[i], flat[(i + 1) % count]);
+        a[0] * b[1] - b[0] * a[1]
+    }).sum();
+    let winding = if area < 0.0 { -1.0 } else { 1.0 };
+    let cross = |a: [f32; 2], b: [f32; 2], c: [f32; 2]| {((b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])) * winding};
+
+    let mut remaining: Vec<usize> = (0..count).collect();
+    let mut triangles = vec![];
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let ear = (0..len).find(|i| {
+            let (prev, cur, next) = (remaining[(i + len - 1) % len], remaining[*i], remaining[(i + 1) % len]);
+            let (a, b, c) = (flat[prev], flat[cur], flat[next]);
+            if cross(a, b, c) <= f32::EPSILON {
+                return false;// reflex or flat, so not an ear
+            }
+            // nothing else can be inside the ear
+            !remaining.iter().any(|p| {
+                if *p == prev || *p == cur || *p == next {
+                    return false;
+                }
+                let p = flat[*p];
+                cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+            })
+        });
+        match ear {
+            Some(i) => {
+                triangles.push([remaining[(i + len - 1) % len], remaining[i], remaining[(i + 1) % len]]);
+                remaining.remove(i);
+            },
+            None => {
+                for i in 1..len - 1 {
+                    triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+                }
+                return triangles;
+            }
+        }
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
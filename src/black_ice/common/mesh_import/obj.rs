@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use crate::black_ice::common::{engine::asset_types::shader_asset::ShaderDataType, vertex::*};
+
+use super::*;
+
+/** A material from an MTL file. Only the parts that the engine can use are kept */
+#[derive(Clone, Default)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub ambient: Option<[f32; 3]>,
+    pub diffuse: Option<[f32; 3]>,
+    pub specular: Option<[f32; 3]>,
+    pub emissive: Option<[f32; 3]>,
+    pub shininess: Option<f32>,
+    pub opacity: Option<f32>,
+    pub textures: Vec<(String, String)>,// the uniform name of the map and the path to it (relative to the MTL file)
+}
+
+impl MtlMaterial {
+
+    pub fn to_imported(&self) -> ImportedMaterial {
+        let textures = self.textures.iter().map(|(sampler, file)| {(sampler.clone(), ImportedTexture::File(file.clone()))}).collect();
+        ImportedMaterial { name: self.name.clone(), params: self.params(), textures }
+    }
+
+    // the uniforms that this material sets, named the same as in the engine's shaders
+    pub fn params(&self) -> Vec<(String, ShaderDataType)> {
+        let mut params = vec![];
+        let colours = [("material.ambient", self.ambient), ("material.diffuse", self.diffuse), ("material.specular", self.specular), ("material.emissive", self.emissive)];
+        for (name, colour) in colours {
+            if let Some(colour) = colour {
+                params.push((name.to_string(), ShaderDataType::Vec3(colour)));
+            }
+        }
+        if let Some(shininess) = self.shininess {
+            params.push(("material.shininess".to_string(), ShaderDataType::Float(shininess)));
+        }
+        if let Some(opacity) = self.opacity {
+            params.push(("material.opacity".to_string(), ShaderDataType::Float(opacity)));
+        }
+        params
+    }
+}
+
+// v/vt/vn index of a face corner, after the negative ones have been resolved
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/** Reads a wavefront OBJ file. Every object (o), group (g) and material change (usemtl) starts a new surface, and
+* polygons with more than 3 sides are triangulated. `load_mtl` is given the name of each mtllib (relative to the OBJ file)
+* and should give back the text of it, or None if it can't be found.
+*/
+pub fn parse_obj(name: String, text: &str, load_mtl: &dyn Fn(&str) -> Option<String>) -> ImportedMesh {
+    let mut positions: Vec<Vec3> = vec![];
+    let mut uvs: Vec<(f32, f32)> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut materials: HashMap<String, MtlMaterial> = HashMap::new();
+    let mut material: Option<String> = None;
+    let mut builder: SurfaceBuilder<Corner> = SurfaceBuilder::new(name);
+
+    for line in logical_lines(text) {
+        let split: Vec<&str> = line.split_whitespace().collect();
+        if split.is_empty() {
+            continue;
+        }
+        match split[0] {
+            "v" => {
+                let v = floats(&split[1..], 3);
+                positions.push(Vec3::new(v[0], v[1], v[2]));
+            },
+            "vt" => {
+                let v = floats(&split[1..], 2);
+                uvs.push((v[0], v[1]));
+            },
+            "vn" => {
+                let v = floats(&split[1..], 3);
+                normals.push(Vec3::new(v[0], v[1], v[2]));
+            },
+            "f" => {
+                let corners: Option<Vec<Corner>> = split[1..].iter().map(|corner| {
+                    let mut parts = corner.split('/');
+                    let position = resolve(parts.next(), positions.len())?;
+                    // a missing or broken uv/normal index just means the corner doesn't have one
+                    let uv = resolve(parts.next(), uvs.len());
+                    let normal = resolve(parts.next(), normals.len());
+                    Some((position, uv, normal))
+                }).collect();
+                let corners = match corners {
+                    Some(c) if c.len() >= 3 => c,
+                    _ => {
+                        println!("Skipping broken face in OBJ file: {}", line);
+                        continue;
+                    }
+                };
+
+                let indices: Vec<u32> = corners.iter().map(|c| {
+                    builder.vertex(*c, positions[c.0], c.2.map(|n| {normals[n]}), c.1.map(|t| {uvs[t]}))
+                }).collect();
+                let points: Vec<Vec3> = corners.iter().map(|c| {positions[c.0]}).collect();
+                for [a, b, c] in triangulate(&points) {
+                    builder.triangle(indices[a], indices[b], indices[c]);
+                }
+            },
+            "o" | "g" => {
+                let name = if split.len() > 1 { split[1..].join(" ") } else { "default".to_string() };
+                builder.start(name, material.clone());
+            },
+            "usemtl" => {
+                material = split.get(1).map(|m| {m.to_string()});
+                let name = builder.get_name().clone();
+                builder.start(name, material.clone());
+            },
+            "mtllib" => {
+                for file in &split[1..] {
+                    match load_mtl(file) {
+                        Some(mtl) => materials.extend(parse_mtl(&mtl)),
+                        None => println!("Couldn't find material library {}!!", file)
+                    }
+                }
+            },
+            _ => {}// smoothing groups, lines and points aren't used
+        }
+    }
+
+    let materials = materials.values().map(|m| {(m.name.clone(), m.to_imported())}).collect();
+    ImportedMesh { surfaces: builder.finish(), materials }
+}
+
+/** Reads the materials out of an MTL file */
+pub fn parse_mtl(text: &str) -> HashMap<String, MtlMaterial> {
+    let mut materials = HashMap::new();
+    let mut current: Option<MtlMaterial> = None;
+
+    for line in logical_lines(text) {
+        let split: Vec<&str> = line.split_whitespace().collect();
+        if split.is_empty() {
+            continue;
+        }
+        if split[0] == "newmtl" {
+            if let Some(done) = current.take() {
+                materials.insert(done.name.clone(), done);
+            }
+            current = Some(MtlMaterial { name: split[1..].join(" "), ..Default::default() });
+            continue;
+        }
+        let material = match current.as_mut() {
+            Some(m) => m,
+            None => continue
+        };
+        let colour = || {
+            // spectral and xyz colours aren't supported
+            if split.len() < 2 || split[1] == "spectral" || split[1] == "xyz" {
+                return None;
+            }
+            let v = floats(&split[1..], 1);
+            // a single value is grey
+            if split.len() < 4 { Some([v[0], v[0], v[0]]) } else { Some([v[0], v[1], v[2]]) }
+        };
+        let value = || {split.get(1).and_then(|v| {v.parse::<f32>().ok()})};
+        // maps can have options before the file name (like -bm 1.0), so the file is always the last part
+        let map = |uniform: &str| {split.last().map(|file| {(uniform.to_string(), file.to_string())})};
+        match split[0] {
+            "Ka" => material.ambient = colour(),
+            "Kd" => material.diffuse = colour(),
+            "Ks" => material.specular = colour(),
+            "Ke" => material.emissive = colour(),
+            "Ns" => material.shininess = value(),
+            "d" => material.opacity = value(),
+            "Tr" => material.opacity = value().map(|t| {1.0 - t}),
+            _ => {
+                let texture = match split[0] {
+                    "map_Ka" => map("material.ambient_map"),
+                    "map_Kd" => map("material.diffuse_map"),
+                    "map_Ks" => map("material.specular_map"),
+                    "map_Ke" => map("material.emissive_map"),
+                    "map_d" => map("material.opacity_map"),
+                    "map_Bump" | "map_bump" | "bump" | "norm" => map("material.normal_map"),
+                    _ => None
+                };
+                if let Some(texture) = texture.filter(|_| {split.len() > 1}) {
+                    material.textures.push(texture);
+                }
+            }
+        }
+    }
+    if let Some(done) = current {
+        materials.insert(done.name.clone(), done);
+    }
+    materials
+}
+
+// the lines of the file without comments, with the lines that end in \ joined on to the next one
+fn logical_lines(text: &str) -> Vec<String> {
+    let mut lines = vec![];
+    let mut pending = String::new();
+    for line in text.lines() {
+        let line = match line.find('#') {
+            Some(i) => &line[..i],
+            None => line
+        };
+        let line = line.trim_end();
+        if let Some(joined) = line.strip_suffix('\\') {
+            pending.push_str(joined);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(line);
+        lines.push(std::mem::take(&mut pending));
+    }
+    if !pending.is_empty() {
+        lines.push(pending);
+    }
+    lines
+}
+
+// reads at least `count` floats, filling in the missing or broken ones with 0
+fn floats(split: &[&str], count: usize) -> Vec<f32> {
+    let mut values: Vec<f32> = split.iter().map(|v| {v.parse::<f32>().unwrap_or(0.0)}).collect();
+    if values.len() < count {
+        values.resize(count, 0.0);
+    }
+    values
+}
+
+// turns an OBJ index (1 based, or negative to count back from the end) into an index into the list
+fn resolve(index: Option<&str>, len: usize) -> Option<usize> {
+    let index = index?.parse::<i64>().ok()?;
+    let resolved = if index < 0 { len as i64 + index } else { index - 1 };
+    if resolved < 0 || resolved >= len as i64 {
+        return None;
+    }
+    Some(resolved as usize)
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use crate::black_ice::common::vertex::*;
+
+use super::*;
+
+#[derive(Clone, Copy, PartialEq)]
+enum PlyFormat {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum PlyType {
+    I8, U8, I16, U16, I32, U32, F32, F64,
+}
+
+impl PlyType {
+
+    fn from_str(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(PlyType::I8),
+            "uchar" | "uint8" => Some(PlyType::U8),
+            "short" | "int16" => Some(PlyType::I16),
+            "ushort" | "uint16" => Some(PlyType::U16),
+            "int" | "int32" => Some(PlyType::I32),
+            "uint" | "uint32" => Some(PlyType::U32),
+            "float" | "float32" => Some(PlyType::F32),
+            "double" | "float64" => Some(PlyType::F64),
+            _ => None
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+
+    // colours are stored as 0 to 255 when they are bytes, and 0 to 1 when they are floats
+    fn colour_scale(&self) -> f64 {
+        match self {
+            PlyType::U8 | PlyType::I8 => 255.0,
+            PlyType::U16 | PlyType::I16 => 65535.0,
+            _ => 1.0
+        }
+    }
+}
+
+struct Property {
+    name: String,
+    data_type: PlyType,
+    list_count: Option<PlyType>,// the type of the length of a list property
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// reads the values of the body one at a time, whatever the format is
+struct PlyReader<'a> {
+    format: PlyFormat,
+    data: &'a [u8],
+    at: usize,
+}
+
+impl<'a> PlyReader<'a> {
+
+    fn next(&mut self, data_type: PlyType) -> Result<f64, String> {
+        if self.format == PlyFormat::Ascii {
+            // skip to the next word
+            while self.at < self.data.len() && self.data[self.at].is_ascii_whitespace() {
+                self.at += 1;
+            }
+            let start = self.at;
+            while self.at < self.data.len() && !self.data[self.at].is_ascii_whitespace() {
+                self.at += 1;
+            }
+            let word = std::str::from_utf8(&self.data[start..self.at]).unwrap_or("");
+            return word.parse::<f64>().map_err(|_| {format!("Expected a number in PLY file, got '{}'!!", word)});
+        }
+
+        let size = data_type.size();
+        let bytes = self.data.get(self.at..self.at + size).ok_or("PLY file ends too soon!!".to_string())?;
+        self.at += size;
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(bytes);
+        if self.format == PlyFormat::BigEndian {
+            buf[..size].reverse();
+        }
+        Ok(match data_type {
+            PlyType::I8 => buf[0] as i8 as f64,
+            PlyType::U8 => buf[0] as f64,
+            PlyType::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            PlyType::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            PlyType::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            PlyType::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            PlyType::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            PlyType::F64 => f64::from_le_bytes(buf),
+        })
+    }
+}
+
+/** Reads a PLY file (ascii, or binary in either endian). The vertex element gives the positions, and the normals, uvs
+* and colours if it has them. The face element is a list of vert indices for each polygon, which gets triangulated.
+* Any other elements are skipped.
+*/
+pub fn parse_ply(name: String, data: &[u8]) -> Result<ImportedMesh, String> {
+    let (format, elements, body) = read_header(data)?;
+    let mut reader = PlyReader { format, data: &data[body..], at: 0 };
+
+    let mut positions: Vec<Vec3> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut uvs: Vec<(f32, f32)> = vec![];
+    let mut colours: Vec<[f32; 4]> = vec![];
+    let mut faces: Vec<Vec<usize>> = vec![];
+
+    for element in &elements {
+        let find = |names: &[&str]| {element.properties.iter().position(|p| {names.contains(&p.name.as_str()) && p.list_count.is_none()})};
+        let (x, y, z) = (find(&["x"]), find(&["y"]), find(&["z"]));
+        let (nx, ny, nz) = (find(&["nx"]), find(&["ny"]), find(&["nz"]));
+        let (u, v) = (find(&["u", "s", "texture_u", "texture_s"]), find(&["v", "t", "texture_v", "texture_t"]));
+        let (r, g, b, a) = (find(&["red", "r"]), find(&["green", "g"]), find(&["blue", "b"]), find(&["alpha", "a"]));
+        let indices = element.properties.iter().position(|p| {(p.name == "vertex_indices" || p.name == "vertex_index") && p.list_count.is_some()});
+
+        for _ in 0..element.count {
+            // every property has to be read to get to the next one, even the ones that aren't used
+            let mut values: Vec<f64> = Vec::with_capacity(element.properties.len());
+            let mut list: Vec<usize> = vec![];
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.list_count {
+                    Some(count_type) => {
+                        let count = reader.next(count_type)? as usize;
+                        for _ in 0..count {
+                            let value = reader.next(property.data_type)?;
+                            if Some(i) == indices {
+                                list.push(value as usize);
+                            }
+                        }
+                        values.push(0.0);
+                    },
+                    None => values.push(reader.next(property.data_type)?)
+                }
+            }
+
+            match element.name.as_str() {
+                "vertex" => {
+                    let get = |p: Option<usize>| {p.map(|p| {values[p] as f32}).unwrap_or(0.0)};
+                    positions.push(Vec3::new(get(x), get(y), get(z)));
+                    if nx.is_some() {
+                        normals.push(Vec3::new(get(nx), get(ny), get(nz)));
+                    }
+                    if u.is_some() {
+                        uvs.push((get(u), get(v)));
+                    }
+                    if r.is_some() {
+                        let channel = |p: Option<usize>| {
+                            p.map(|p| {(values[p] / element.properties[p].data_type.colour_scale()) as f32}).unwrap_or(1.0)
+                        };
+                        colours.push([channel(r), channel(g), channel(b), channel(a)]);
+                    }
+                },
+                "face" => faces.push(list),
+                _ => {}
+            }
+        }
+    }
+
+    let mut builder: SurfaceBuilder<usize> = SurfaceBuilder::new(name);
+    for face in faces {
+        if face.len() < 3 || face.iter().any(|i| {*i >= positions.len()}) {
+            println!("Skipping broken face in PLY file!!");
+            continue;
+        }
+        let indices: Vec<u32> = face.iter().map(|i| {
+            let index = builder.vertex(*i, positions[*i], normals.get(*i).copied(), uvs.get(*i).copied());
+            if let Some(colour) = colours.get(*i) {
+                builder.colour(index, *colour);
+            }
+            index
+        }).collect();
+        let points: Vec<Vec3> = face.iter().map(|i| {positions[*i]}).collect();
+        for [a, b, c] in triangulate(&points) {
+            builder.triangle(indices[a], indices[b], indices[c]);
+        }
+    }
+    Ok(ImportedMesh { surfaces: builder.finish(), materials: HashMap::new() })
+}
+
+// gives back the format, the elements, and where the body starts
+fn read_header(data: &[u8]) -> Result<(PlyFormat, Vec<Element>, usize), String> {
+    if !data.starts_with(b"ply") {
+        return Err("Not a PLY file!!".to_string());
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut at = 0;
+    loop {
+        let end = data[at..].iter().position(|c| {*c == b'\n'}).ok_or("PLY header has no end_header!!".to_string())?;
+        let line = String::from_utf8_lossy(&data[at..at + end]).to_string();
+        at += end + 1;
+        let split: Vec<&str> = line.split_whitespace().collect();
+        match split.as_slice() {
+            ["end_header"] => break,
+            ["format", kind, ..] => {
+                format = Some(match *kind {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::LittleEndian,
+                    "binary_big_endian" => PlyFormat::BigEndian,
+                    _ => return Err(format!("Unknown PLY format {}!!", kind))
+                });
+            },
+            ["element", name, count] => {
+                let count = count.parse::<usize>().map_err(|_| {format!("Broken element count in PLY file: {}", line)})?;
+                elements.push(Element { name: name.to_string(), count, properties: vec![] });
+            },
+            ["property", "list", count_type, data_type, name] => {
+                let element = elements.last_mut().ok_or("PLY property has no element!!".to_string())?;
+                let count_type = PlyType::from_str(count_type).ok_or(format!("Unknown PLY type {}!!", count_type))?;
+                let data_type = PlyType::from_str(data_type).ok_or(format!("Unknown PLY type {}!!", data_type))?;
+                element.properties.push(Property { name: name.to_string(), data_type, list_count: Some(count_type) });
+            },
+            ["property", data_type, name] => {
+                let element = elements.last_mut().ok_or("PLY property has no element!!".to_string())?;
+                let data_type = PlyType::from_str(data_type).ok_or(format!("Unknown PLY type {}!!", data_type))?;
+                element.properties.push(Property { name: name.to_string(), data_type, list_count: None });
+            },
+            _ => {}// comments and obj_info
+        }
+    }
+    Ok((format.ok_or("PLY header has no format!!".to_string())?, elements, at))
+}
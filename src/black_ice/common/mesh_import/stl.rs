@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use crate::black_ice::common::vertex::*;
+
+use super::*;
+
+// a vert is only shared between triangles when they have the same normal (and colour), so that the faces stay flat
+type Corner = ([u32; 3], [u32; 3], Option<[u32; 4]>);
+
+/** Reads an STL file, either ascii or binary. STL only has a normal for each triangle, so every triangle is flat. Binary
+* files can have a colour for each triangle (both the VisCAM and Materialise ways are read), which goes into the
+* vertex colours.
+*/
+pub fn parse_stl(name: String, data: &[u8]) -> Result<ImportedMesh, String> {
+    let triangles = if is_binary(data) { read_binary(data)? } else { read_ascii(data)? };
+
+    let mut builder: SurfaceBuilder<Corner> = SurfaceBuilder::new(name);
+    for (normal, verts, colour) in triangles {
+        // lots of exporters leave the normal as 0, so work it out from the triangle
+        let normal = if normal.magnitude() > f32::EPSILON { normal.normalized() }
+            else { (verts[1] - verts[0]).cross(verts[2] - verts[0]).normalized() };
+        let corners = verts.map(|v| {
+            let key = ([v.x.to_bits(), v.y.to_bits(), v.z.to_bits()], [normal.x.to_bits(), normal.y.to_bits(), normal.z.to_bits()], colour.map(|c| {c.map(|c| {c.to_bits()})}));
+            let index = builder.vertex(key, v, Some(normal), None);
+            if let Some(colour) = colour {
+                builder.colour(index, colour);
+            }
+            index
+        });
+        builder.triangle(corners[0], corners[1], corners[2]);
+    }
+    Ok(ImportedMesh { surfaces: builder.finish(), materials: HashMap::new() })
+}
+
+type Triangle = (Vec3, [Vec3; 3], Option<[f32; 4]>);
+
+// ascii files start with "solid", but so do some binary ones!! The size of a binary file always matches its triangle count
+fn is_binary(data: &[u8]) -> bool {
+    if data.len() < 84 {
+        return false;
+    }
+    let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+    if data.len() == 84 + count * 50 {
+        return true;
+    }
+    !data.trim_ascii_start().starts_with(b"solid")
+}
+
+fn read_binary(data: &[u8]) -> Result<Vec<Triangle>, String> {
+    let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+    if data.len() < 84 + count * 50 {
+        return Err(format!("Binary STL says it has {} triangles, but is too short!!", count));
+    }
+    // Materialise put a default colour in the header, and use bit 15 the other way round to VisCAM
+    let header = &data[..80];
+    let materialise = header.windows(6).any(|w| {w == b"COLOR="});
+
+    let float = |at: usize| {f32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])};
+    let vec = |at: usize| {Vec3::new(float(at), float(at + 4), float(at + 8))};
+    Ok((0..count).map(|i| {
+        let at = 84 + i * 50;
+        let attribute = u16::from_le_bytes([data[at + 48], data[at + 49]]);
+        let channel = |shift: u16| {((attribute >> shift) & 0x1f) as f32 / 31.0};
+        let colour = if materialise && attribute & 0x8000 == 0 {
+            Some([channel(0), channel(5), channel(10), 1.0])
+        }
+        else if !materialise && attribute & 0x8000 != 0 {
+            Some([channel(10), channel(5), channel(0), 1.0])
+        }
+        else {
+            None
+        };
+        (vec(at), [vec(at + 12), vec(at + 24), vec(at + 36)], colour)
+    }).collect())
+}
+
+fn read_ascii(data: &[u8]) -> Result<Vec<Triangle>, String> {
+    let text = String::from_utf8_lossy(data);
+    let mut triangles = vec![];
+    let mut normal = Vec3::new(0.0, 0.0, 0.0);
+    let mut verts: Vec<Vec3> = vec![];
+    for line in text.lines() {
+        let split: Vec<&str> = line.split_whitespace().collect();
+        let floats = |from: usize| -> Result<Vec3, String> {
+            let value = |i: usize| {split.get(i).and_then(|v| {v.parse::<f32>().ok()}).ok_or(format!("Broken line in STL file: {}", line))};
+            Ok(Vec3::new(value(from)?, value(from + 1)?, value(from + 2)?))
+        };
+        match split.first() {
+            Some(&"facet") => {
+                normal = floats(2)?;
+                verts.clear();
+            },
+            Some(&"vertex") => verts.push(floats(1)?),
+            Some(&"endfacet") => {
+                if verts.len() != 3 {
+                    return Err(format!("STL facet has {} verts, it should have 3!!", verts.len()));
+                }
+                triangles.push((normal, [verts[0], verts[1], verts[2]], None));
+            },
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
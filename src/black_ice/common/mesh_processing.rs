@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+
+use crate::black_ice::common::{matrices::Matrix34, mesh::*, vertex::*};
+
+/** A box around a surface (or a whole mesh), lined up with the axes */
+#[derive(Copy, Clone)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+
+    // a box with nothing in it, so that anything added to it becomes the box
+    pub fn empty() -> Self {
+        Aabb { min: Vec3::new(f32::MAX, f32::MAX, f32::MAX), max: Vec3::new(f32::MIN, f32::MIN, f32::MIN) }
+    }
+
+    pub fn from_points(points: &[Vec3]) -> Self {
+        let mut aabb = Self::empty();
+        for point in points {
+            aabb.add_point(*point);
+        }
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn add_point(&mut self, point: Vec3) {
+        self.min = Vec3::new(self.min.x.min(point.x), self.min.y.min(point.y), self.min.z.min(point.z));
+        self.max = Vec3::new(self.max.x.max(point.x), self.max.y.max(point.y), self.max.z.max(point.z));
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        if other.is_empty() {
+            return *self;
+        }
+        let mut aabb = *self;
+        aabb.add_point(other.min);
+        aabb.add_point(other.max);
+        aabb
+    }
+
+    pub fn centre(&self) -> Vec3 {
+        (self.min + self.max).times(0.5)
+    }
+
+    // half the size of the box on each axis
+    pub fn extents(&self) -> Vec3 {
+        (self.max - self.min).times(0.5)
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.x >= self.min.x && point.y >= self.min.y && point.z >= self.min.z &&
+            point.x <= self.max.x && point.y <= self.max.y && point.z <= self.max.z
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x &&
+            self.min.y <= other.max.y && self.max.y >= other.min.y &&
+            self.min.z <= other.max.z && self.max.z >= other.min.z
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vec3::new(a.x, a.y, a.z), Vec3::new(b.x, a.y, a.z), Vec3::new(a.x, b.y, a.z), Vec3::new(b.x, b.y, a.z),
+            Vec3::new(a.x, a.y, b.z), Vec3::new(b.x, a.y, b.z), Vec3::new(a.x, b.y, b.z), Vec3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    // the box around this one once it has been moved by a transform. It can be bigger than the box around the
+    // transformed verts would be, but it is always big enough for culling
+    pub fn transformed(&self, transform: &Matrix34) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+        let corners = self.corners().map(|c| {*transform * c});
+        Self::from_points(&corners)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct BoundingSphere {
+    pub centre: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+
+    /** Finds a sphere around the points with ritter's method. It isn't the smallest sphere there is, but it is
+    * normally within a few percent of it, and it only needs a couple of passes over the points
+    */
+    pub fn from_points(points: &[Vec3]) -> Self {
+        if points.is_empty() {
+            return BoundingSphere { centre: Vec3::new(0.0, 0.0, 0.0), radius: 0.0 };
+        }
+        let furthest = |from: Vec3| {
+            *points.iter().max_by(|a, b| {(**a - from).magnitude().total_cmp(&(**b - from).magnitude())}).unwrap()
+        };
+        let a = furthest(points[0]);
+        let b = furthest(a);
+        let mut centre = (a + b).times(0.5);
+        let mut radius = (b - a).magnitude() * 0.5;
+
+        // grow the sphere to take in anything that is still outside it
+        for point in points {
+            let distance = (*point - centre).magnitude();
+            if distance > radius {
+                let new_radius = (radius + distance) * 0.5;
+                centre += (*point - centre).times((new_radius - radius) / distance);
+                radius = new_radius;
+            }
+        }
+        BoundingSphere { centre, radius }
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        (point - self.centre).magnitude() <= self.radius
+    }
+
+    pub fn union(&self, other: &BoundingSphere) -> BoundingSphere {
+        let offset = other.centre - self.centre;
+        let distance = offset.magnitude();
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+        let radius = (distance + self.radius + other.radius) * 0.5;
+        let centre = self.centre + offset.times((radius - self.radius) / distance);
+        BoundingSphere { centre, radius }
+    }
+}
+
+/** Things that can be done to a surface after it has been built or imported, so that it is ready to be rendered.
+* The surface has to be made of triangles for the normals and tangents.
+*/
+pub trait MeshProcess {
+    // smooth normals are shared by every triangle that touches a position, flat ones give each triangle its own verts
+    fn generate_normals(&mut self, smooth: bool);
+    fn generate_tangents(&mut self);
+    // merges the verts that are within `tolerance` of each other in every attribute, giving back how many were removed
+    fn weld(&mut self, tolerance: f32) -> usize;
+    fn has_normals(&self) -> bool;
+    fn bounds(&self) -> Aabb;
+    fn bounding_sphere(&self) -> BoundingSphere;
+}
+
+impl MeshProcess for Surface {
+
+    fn generate_normals(&mut self, smooth: bool) {
+        if !smooth {
+            split_triangles(self);
+        }
+        let count = self.verts.len();
+        let mut normals = vec![Vec3::new(0.0, 0.0, 0.0); count];
+
+        // verts that are in the same place but have different uvs (a seam) still need the same normal, or the seam
+        // shows up in the lighting!! So they are added up by position, not by vert
+        let mut positions: HashMap<[u32; 3], usize> = HashMap::new();
+        let group: Vec<usize> = self.verts.iter().enumerate().map(|(i, v)| {
+            if !smooth {
+                return i;
+            }
+            *positions.entry([v.x.to_bits(), v.y.to_bits(), v.z.to_bits()]).or_insert(i)
+        }).collect();
+
+        for [a, b, c] in triangles(self) {
+            let (pa, pb, pc) = (self.verts[a], self.verts[b], self.verts[c]);
+            let face = (pb - pa).cross(pc - pa);
+            if face.magnitude() <= f32::EPSILON {
+                continue;
+            }
+            let face = face.normalized();
+            // weighting by the angle at each corner means that splitting a face up doesn't change the normal
+            for (corner, prev, next) in [(a, c, b), (b, a, c), (c, b, a)] {
+                let p = self.verts[corner];
+                let angle = corner_angle(self.verts[prev] - p, self.verts[next] - p);
+                normals[group[corner]] += face.times(angle);
+            }
+        }
+
+        self.normals = (0..count).map(|i| {
+            let normal = normals[group[i]];
+            if normal.magnitude() > f32::EPSILON { normal.normalized() } else { Vec3::new(0.0, 0.0, 1.0) }
+        }).collect();
+    }
+
+    /** Works out the tangents the same way as mikktspace: the direction that u goes in on each triangle is added up
+    * for each vert (weighted by the corner angle), then made to be at right angles to the normal. w says whether the
+    * bitangent is cross(normal, tangent) or the other way, for when the uvs have been mirrored.
+    */
+    fn generate_tangents(&mut self) {
+        let count = self.verts.len();
+        if !self.has_normals() {
+            self.generate_normals(true);
+        }
+        let mut tangents = vec![Vec3::new(0.0, 0.0, 0.0); count];
+        let mut bitangents = vec![Vec3::new(0.0, 0.0, 0.0); count];
+
+        for [a, b, c] in triangles(self) {
+            let (pa, pb, pc) = (self.verts[a], self.verts[b], self.verts[c]);
+            let (ta, tb, tc) = (self.texture_coord[a], self.texture_coord[b], self.texture_coord[c]);
+            let (e1, e2) = (pb - pa, pc - pa);
+            let (du1, dv1, du2, dv2) = (tb.0 - ta.0, tb.1 - ta.1, tc.0 - ta.0, tc.1 - ta.1);
+            let det = du1 * dv2 - du2 * dv1;
+            if det.abs() <= f32::EPSILON {
+                continue;// the uvs are squashed flat, so there is no direction to take
+            }
+            let r = 1.0 / det;
+            let tangent = (e1.times(dv2) - e2.times(dv1)).times(r);
+            let bitangent = (e2.times(du1) - e1.times(du2)).times(r);
+            for (corner, prev, next) in [(a, c, b), (b, a, c), (c, b, a)] {
+                let p = self.verts[corner];
+                let angle = corner_angle(self.verts[prev] - p, self.verts[next] - p);
+                tangents[corner] += tangent.times(angle);
+                bitangents[corner] += bitangent.times(angle);
+            }
+        }
+
+        self.tangents = (0..count).map(|i| {
+            let normal = self.normals[i];
+            // gram-schmidt, so the tangent lies flat on the surface
+            let mut tangent = tangents[i] - normal.times(normal.dot(tangents[i]));
+            if tangent.magnitude() <= f32::EPSILON {
+                tangent = perpendicular(normal);
+            }
+            let tangent = tangent.normalized();
+            let w = if normal.cross(tangent).dot(bitangents[i]) < 0.0 { -1.0 } else { 1.0 };
+            [tangent.x, tangent.y, tangent.z, w]
+        }).collect();
+    }
+
+    fn weld(&mut self, tolerance: f32) -> usize {
+        let count = self.verts.len();
+        let tolerance = tolerance.max(0.0);
+        // the verts are put into a grid of cells as big as the tolerance, so only the cells next to a vert need checking
+        let cell_size = if tolerance > 0.0 { tolerance } else { 1.0 };
+        let cell = |v: Vec3| {[(v.x / cell_size).floor() as i64, (v.y / cell_size).floor() as i64, (v.z / cell_size).floor() as i64]};
+
+        let mut grid: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+        let mut remap: Vec<u32> = Vec::with_capacity(count);
+        let mut kept: Vec<usize> = vec![];
+        for i in 0..count {
+            let [x, y, z] = cell(self.verts[i]);
+            let mut found = None;
+            'search: for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        for other in grid.get(&[x + dx, y + dy, z + dz]).into_iter().flatten() {
+                            if same_vert(self, i, kept[*other as usize], tolerance) {
+                                found = Some(*other);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+            let index = match found {
+                Some(index) => index,
+                None => {
+                    kept.push(i);
+                    let index = (kept.len() - 1) as u32;
+                    grid.entry([x, y, z]).or_default().push(index);
+                    index
+                }
+            };
+            remap.push(index);
+        }
+
+        // the triangles that have been squashed down to a line or a point by the weld are dropped
+        let mut indices = IndexBuffer::new();
+        for [a, b, c] in triangles(self) {
+            let (a, b, c) = (remap[a], remap[b], remap[c]);
+            if a != b && b != c && c != a {
+                indices.push(a);
+                indices.push(b);
+                indices.push(c);
+            }
+        }
+        self.indices = indices;
+
+        fn keep<T: Copy>(stream: &mut Vec<T>, kept: &[usize], count: usize) {
+            if stream.len() == count {
+                *stream = kept.iter().map(|i| {stream[*i]}).collect();
+            }
+        }
+        keep(&mut self.verts, &kept, count);
+        keep(&mut self.normals, &kept, count);
+        keep(&mut self.tangents, &kept, count);
+        keep(&mut self.texture_coord, &kept, count);
+        keep(&mut self.texture_coord1, &kept, count);
+        keep(&mut self.colours, &kept, count);
+        count - kept.len()
+    }
+
+    // importers leave the normals as 0 when the file doesn't have them
+    fn has_normals(&self) -> bool {
+        self.normals.len() == self.verts.len() && self.normals.iter().all(|n| {n.magnitude() > f32::EPSILON})
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::from_points(&self.verts)
+    }
+
+    fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere::from_points(&self.verts)
+    }
+}
+
+impl Mesh {
+
+    // the box around every surface of the mesh, before the mesh's transform
+    pub fn bounds(&self) -> Aabb {
+        self.surfaces.iter().fold(Aabb::empty(), |aabb, surface| {aabb.union(&surface.lock().bounds())})
+    }
+
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        let verts: Vec<Vec3> = self.surfaces.iter().flat_map(|surface| {surface.lock().verts.clone()}).collect();
+        BoundingSphere::from_points(&verts)
+    }
+}
+
+fn triangles(surface: &Surface) -> Vec<[usize; 3]> {
+    (0..surface.indices.len() / 3).map(|t| {
+        [surface.indices.get(t * 3) as usize, surface.indices.get(t * 3 + 1) as usize, surface.indices.get(t * 3 + 2) as usize]
+    }).collect()
+}
+
+// the angle between two edges of a triangle, 0 when one of them has no length
+fn corner_angle(a: Vec3, b: Vec3) -> f32 {
+    let (mag_a, mag_b) = (a.magnitude(), b.magnitude());
+    if mag_a <= f32::EPSILON || mag_b <= f32::EPSILON {
+        return 0.0;
+    }
+    (a.dot(b) / (mag_a * mag_b)).clamp(-1.0, 1.0).acos()
+}
+
+// any direction at right angles to the normal, for verts that don't have a tangent of their own
+fn perpendicular(normal: Vec3) -> Vec3 {
+    let axis = if normal.x.abs() < 0.9 { Vec3::new(1.0, 0.0, 0.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+    let tangent = normal.cross(axis).cross(normal);
+    if tangent.magnitude() > f32::EPSILON { tangent } else { axis }
+}
+
+// gives every triangle its own 3 verts, so that nothing is shared between them
+fn split_triangles(surface: &mut Surface) {
+    let corners: Vec<usize> = triangles(surface).concat();
+    let count = surface.verts.len();
+    fn split<T: Copy>(stream: &mut Vec<T>, corners: &[usize], count: usize) {
+        if stream.len() == count {
+            *stream = corners.iter().map(|i| {stream[*i]}).collect();
+        }
+    }
+    split(&mut surface.verts, &corners, count);
+    split(&mut surface.normals, &corners, count);
+    split(&mut surface.tangents, &corners, count);
+    split(&mut surface.texture_coord, &corners, count);
+    split(&mut surface.texture_coord1, &corners, count);
+    split(&mut surface.colours, &corners, count);
+    surface.indices = IndexBuffer::new();
+    for i in 0..corners.len() {
+        surface.indices.push(i as u32);
+    }
+}
+
+fn same_vert(surface: &Surface, a: usize, b: usize, tolerance: f32) -> bool {
+    let close = |x: f32, y: f32| {(x - y).abs() <= tolerance};
+    let close3 = |x: Vec3, y: Vec3| {close(x.x, y.x) && close(x.y, y.y) && close(x.z, y.z)};
+    let close4 = |x: [f32; 4], y: [f32; 4]| {(0..4).all(|i| {close(x[i], y[i])})};
+    let close2 = |x: (f32, f32), y: (f32, f32)| {close(x.0, y.0) && close(x.1, y.1)};
+    let count = surface.verts.len();
+
+    close3(surface.verts[a], surface.verts[b]) &&
+        (surface.normals.len() != count || close3(surface.normals[a], surface.normals[b])) &&
+        (surface.tangents.len() != count || close4(surface.tangents[a], surface.tangents[b])) &&
+        (surface.texture_coord.len() != count || close2(surface.texture_coord[a], surface.texture_coord[b])) &&
+        (surface.texture_coord1.len() != count || close2(surface.texture_coord1[a], surface.texture_coord1[b])) &&
+        (surface.colours.len() != count || close4(surface.colours[a], surface.colours[b]))
+}
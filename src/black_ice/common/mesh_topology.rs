@@ -0,0 +1,416 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::black_ice::common::{mesh::*, transform::Transform, vertex::*};
+
+/** One side of an edge of a triangle. Half edge 3 * f + k goes from corner k of face f to corner k + 1, so the face,
+* next and prev of a half edge don't need storing
+*/
+#[derive(Clone, Copy)]
+pub struct HalfEdge {
+    pub origin: u32,
+    pub twin: Option<u32>,// the half edge going the other way along the same edge, None on the edge of the surface
+    pub edge: u32,
+}
+
+/** The connections between the verts, edges and faces of a surface, made out of half edges.
+*
+* Importers split a vert in two wherever the uvs or normals change (a seam), but the surface is still joined up there.
+* So the topology works on positions: every vert is joined to the first vert that is in the same place, and that is
+* the vert index that comes back from the queries. Faces are the triangles of the surface, in the index buffer's order.
+*
+* Edges that have more than 2 faces on them (non-manifold) only get joined to one of the other faces!!
+*/
+pub struct Topology {
+    pub half_edges: Vec<HalfEdge>,
+    pub edges: Vec<(u32, u32)>,// the verts at each end, the smaller one first
+    edge_half: Vec<u32>,// one of the half edges of each edge
+    shared_vert: Vec<u32>,// the vert that each vert is joined to
+    out_start: Vec<u32>,// out[out_start[v]..out_start[v + 1]] are the half edges that start at v
+    out: Vec<u32>,
+    vert_count: usize,
+    index_count: usize,
+}
+
+impl Topology {
+
+    pub fn new(surface: &Surface) -> Self {
+        let vert_count = surface.verts.len();
+        let index_count = surface.indices.len() - surface.indices.len() % 3;
+
+        let mut positions: HashMap<[u32; 3], u32> = HashMap::new();
+        let shared_vert: Vec<u32> = surface.verts.iter().enumerate().map(|(i, v)| {
+            *positions.entry([v.x.to_bits(), v.y.to_bits(), v.z.to_bits()]).or_insert(i as u32)
+        }).collect();
+
+        let origins: Vec<u32> = (0..index_count).map(|i| {shared_vert[surface.indices.get(i) as usize]}).collect();
+        let dest = |h: usize| {origins[next(h as u32) as usize]};
+
+        let mut half_edges = Vec::with_capacity(index_count);
+        let mut edges: Vec<(u32, u32)> = vec![];
+        let mut edge_half: Vec<u32> = vec![];
+        let mut edge_ids: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut sides: HashMap<(u32, u32), u32> = HashMap::new();
+        for h in 0..index_count {
+            let (a, b) = (origins[h], dest(h));
+            let key = (a.min(b), a.max(b));
+            let edge = *edge_ids.entry(key).or_insert_with(|| {
+                edges.push(key);
+                edge_half.push(h as u32);
+                (edges.len() - 1) as u32
+            });
+            sides.entry((a, b)).or_insert(h as u32);
+            half_edges.push(HalfEdge { origin: a, twin: None, edge });
+        }
+        for h in 0..index_count {
+            let twin = sides.get(&(dest(h), origins[h])).copied();
+            // only join them up when they agree, so a non-manifold edge doesn't get half of a pair
+            if twin.map(|t| {sides.get(&(origins[t as usize], dest(t as usize))) == Some(&(h as u32))}).unwrap_or(false) {
+                half_edges[h].twin = twin;
+            }
+        }
+
+        // sort the half edges by the vert they start at, so the ones around a vert can be found straight away
+        let mut out_start = vec![0u32; vert_count + 1];
+        for h in &half_edges {
+            out_start[h.origin as usize + 1] += 1;
+        }
+        for v in 0..vert_count {
+            out_start[v + 1] += out_start[v];
+        }
+        let mut filled = out_start.clone();
+        let mut out = vec![0u32; half_edges.len()];
+        for (h, half_edge) in half_edges.iter().enumerate() {
+            out[filled[half_edge.origin as usize] as usize] = h as u32;
+            filled[half_edge.origin as usize] += 1;
+        }
+
+        Topology { half_edges, edges, edge_half, shared_vert, out_start, out, vert_count, index_count }
+    }
+
+    // whether the surface has had verts or faces added or taken away since this was made
+    pub fn is_out_of_date(&self, surface: &Surface) -> bool {
+        self.vert_count != surface.verts.len() || self.index_count != surface.indices.len() - surface.indices.len() % 3
+    }
+
+    // the vert that `vert` is joined to, which is the one the queries use
+    pub fn shared_vert(&self, vert: u32) -> u32 {
+        self.shared_vert[vert as usize]
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.index_count / 3
+    }
+
+    pub fn face(&self, half_edge: u32) -> u32 {
+        half_edge / 3
+    }
+
+    // where a half edge ends
+    pub fn dest(&self, half_edge: u32) -> u32 {
+        self.half_edges[next(half_edge) as usize].origin
+    }
+
+    // the half edges that start at a vert
+    pub fn outgoing(&self, vert: u32) -> &[u32] {
+        let vert = self.shared_vert(vert) as usize;
+        &self.out[self.out_start[vert] as usize..self.out_start[vert + 1] as usize]
+    }
+
+    pub fn find_edge(&self, a: u32, b: u32) -> Option<u32> {
+        let (a, b) = (self.shared_vert(a), self.shared_vert(b));
+        self.edges_of_vert(a).into_iter().find(|e| {self.edges[*e as usize] == (a.min(b), a.max(b))})
+    }
+
+    pub fn is_boundary(&self, edge: u32) -> bool {
+        self.half_edges[self.edge_half[edge as usize] as usize].twin.is_none()
+    }
+
+    pub fn edges_of_vert(&self, vert: u32) -> Vec<u32> {
+        let mut edges = vec![];
+        for h in self.outgoing(vert) {
+            // the half edge before one that starts at the vert ends at it
+            for h in [*h, prev(*h)] {
+                let edge = self.half_edges[h as usize].edge;
+                if !edges.contains(&edge) {
+                    edges.push(edge);
+                }
+            }
+        }
+        edges
+    }
+
+    pub fn faces_of_vert(&self, vert: u32) -> Vec<u32> {
+        self.outgoing(vert).iter().map(|h| {self.face(*h)}).collect()
+    }
+
+    pub fn faces_of_edge(&self, edge: u32) -> Vec<u32> {
+        let h = self.edge_half[edge as usize];
+        let mut faces = vec![self.face(h)];
+        if let Some(twin) = self.half_edges[h as usize].twin {
+            faces.push(self.face(twin));
+        }
+        faces
+    }
+
+    // the faces that share an edge with a face
+    pub fn neighbouring_faces(&self, face: u32) -> Vec<u32> {
+        (face * 3..face * 3 + 3).filter_map(|h| {self.half_edges[h as usize].twin.map(|t| {self.face(t)})}).collect()
+    }
+
+    /** The edges that carry on in a line from an edge, in order. Away from the edge of the surface, the loop goes
+    * straight through every vert that has an even number of edges (the one half way round from where it came in), so it
+    * follows the rows of a grid whether it is quads or triangles. It stops at verts with an odd number of edges.
+    * A loop started on the edge of the surface goes round the whole border (or hole) instead.
+    */
+    pub fn edge_loop(&self, edge: u32) -> Vec<u32> {
+        if self.is_boundary(edge) {
+            return self.boundary_loop(edge);
+        }
+        let (a, b) = self.edges[edge as usize];
+        let mut edges = vec![edge];
+        let mut seen: HashSet<u32> = HashSet::from([edge]);
+        for (forwards, start) in [(true, b), (false, a)] {
+            let (mut current, mut vert) = (edge, start);
+            while let Some(next_edge) = self.continue_loop(current, vert) {
+                if next_edge == edge {
+                    return edges;// it has come all the way round
+                }
+                if !seen.insert(next_edge) {
+                    break;
+                }
+                if forwards { edges.push(next_edge) } else { edges.insert(0, next_edge) }
+                let (x, y) = self.edges[next_edge as usize];
+                vert = if x == vert { y } else { x };
+                current = next_edge;
+            }
+        }
+        edges
+    }
+
+    // the edge on the other side of `vert` from `edge`, if the vert is inside the surface and has an even number of edges
+    fn continue_loop(&self, edge: u32, vert: u32) -> Option<u32> {
+        let outgoing = self.outgoing(vert);
+        let start = *outgoing.iter().find(|h| {self.half_edges[**h as usize].edge == edge})?;
+        // go round the fan of triangles around the vert
+        let mut fan = vec![start];
+        let mut h = start;
+        loop {
+            h = self.half_edges[prev(h) as usize].twin?;
+            if h == start {
+                break;
+            }
+            fan.push(h);
+            if fan.len() > outgoing.len() {
+                return None;
+            }
+        }
+        if fan.len() != outgoing.len() || fan.len() % 2 != 0 {
+            return None;
+        }
+        Some(self.half_edges[fan[fan.len() / 2] as usize].edge)
+    }
+
+    fn boundary_loop(&self, edge: u32) -> Vec<u32> {
+        let start = self.edge_half[edge as usize];
+        let mut edges = vec![edge];
+        let mut h = start;
+        loop {
+            let vert = self.dest(h);
+            let next_half = self.outgoing(vert).iter().find(|o| {self.half_edges[**o as usize].twin.is_none()});
+            match next_half {
+                Some(next_half) if *next_half != start => {
+                    h = *next_half;
+                    let next_edge = self.half_edges[h as usize].edge;
+                    if edges.contains(&next_edge) {
+                        break;
+                    }
+                    edges.push(next_edge);
+                },
+                _ => break
+            }
+        }
+        edges
+    }
+}
+
+fn next(half_edge: u32) -> u32 {
+    if half_edge % 3 == 2 { half_edge - 2 } else { half_edge + 1 }
+}
+
+fn prev(half_edge: u32) -> u32 {
+    if half_edge % 3 == 0 { half_edge + 2 } else { half_edge - 1 }
+}
+
+impl MeshRender for Surface {
+
+    fn get_vert(&self, i: u32) -> Vec3 {
+        self.verts[i as usize]
+    }
+
+    fn get_verts(&self) -> Vec<Vec3> {
+        self.verts.clone()
+    }
+
+    fn get_edges(&self) -> Vec<(u32, u32)> {
+        self.topology().edges.clone()
+    }
+
+    fn get_edge(&self, i: u32) -> (u32, u32) {
+        self.topology().edges[i as usize]
+    }
+
+    fn get_edges_of_vert(&self, i: u32) -> Vec<(u32, u32)> {
+        let topology = self.topology();
+        topology.edges_of_vert(i).iter().map(|e| {topology.edges[*e as usize]}).collect()
+    }
+
+    fn get_face(&self, i: u32) -> (u32, u32, u32) {
+        let i = i as usize * 3;
+        (self.indices.get(i), self.indices.get(i + 1), self.indices.get(i + 2))
+    }
+
+    fn get_faces(&self, i: u32) -> Vec<u32> {
+        self.topology().faces_of_vert(i)
+    }
+
+    fn get_verts_of_face(&self, i: u32) -> Vec<u32> {
+        let (a, b, c) = self.get_face(i);
+        vec![a, b, c]
+    }
+
+    fn get_neighbouring_faces(&self, i: u32) -> Vec<u32> {
+        self.topology().neighbouring_faces(i)
+    }
+
+    fn get_edge_loop(&self, i: u32) -> Vec<u32> {
+        self.topology().edge_loop(i)
+    }
+
+    fn translated_verts(&self, t: &Transform) -> Vec<Vec3> {
+        let tm = t.get_world_tm();
+        self.verts.iter().map(|v| {tm * *v}).collect()
+    }
+
+    fn translated_faces(&self, t: &Transform) -> Vec<(Vec3, Vec3, Vec3)> {
+        let verts = self.translated_verts(t);
+        (0..self.indices.len() as u32 / 3).map(|f| {
+            let (a, b, c) = self.get_face(f);
+            (verts[a as usize], verts[b as usize], verts[c as usize])
+        }).collect()
+    }
+
+    fn translated_edges(&self, t: &Transform) -> Vec<(Vec3, Vec3)> {
+        let verts = self.translated_verts(t);
+        self.get_edges().iter().map(|(a, b)| {(verts[*a as usize], verts[*b as usize])}).collect()
+    }
+}
+
+/** A mesh is queried as if its surfaces were one after the other: the verts of the second surface are numbered on from
+* the last vert of the first, and the same for the edges and faces. Surfaces aren't joined to each other, even where
+* they touch.
+*/
+impl MeshRender for Mesh {
+
+    fn get_vert(&self, i: u32) -> Vec3 {
+        let (surface, local, _) = self.locate(i, |s| {s.verts.len()});
+        let vert = surface.lock().get_vert(local);
+        vert
+    }
+
+    fn get_verts(&self) -> Vec<Vec3> {
+        self.surfaces.iter().flat_map(|s| {s.lock().get_verts()}).collect()
+    }
+
+    fn get_edges(&self) -> Vec<(u32, u32)> {
+        let mut edges = vec![];
+        let mut offset = 0;
+        for surface in &self.surfaces {
+            let surface = surface.lock();
+            edges.extend(surface.get_edges().iter().map(|(a, b)| {(a + offset, b + offset)}));
+            offset += surface.verts.len() as u32;
+        }
+        edges
+    }
+
+    fn get_edge(&self, i: u32) -> (u32, u32) {
+        let (surface, local, _) = self.locate(i, |s| {s.topology().edges.len()});
+        let offset = self.vert_offset(&surface);
+        let (a, b) = surface.lock().get_edge(local);
+        (a + offset, b + offset)
+    }
+
+    fn get_edges_of_vert(&self, i: u32) -> Vec<(u32, u32)> {
+        let (surface, local, offset) = self.locate(i, |s| {s.verts.len()});
+        let edges = surface.lock().get_edges_of_vert(local);
+        edges.iter().map(|(a, b)| {(a + offset, b + offset)}).collect()
+    }
+
+    fn get_face(&self, i: u32) -> (u32, u32, u32) {
+        let (surface, local, _) = self.locate(i, |s| {s.indices.len() / 3});
+        let offset = self.vert_offset(&surface);
+        let (a, b, c) = surface.lock().get_face(local);
+        (a + offset, b + offset, c + offset)
+    }
+
+    fn get_faces(&self, i: u32) -> Vec<u32> {
+        let (surface, local, _) = self.locate(i, |s| {s.verts.len()});
+        let offset = self.face_offset(&surface);
+        let faces = surface.lock().get_faces(local);
+        faces.iter().map(|f| {f + offset}).collect()
+    }
+
+    fn get_verts_of_face(&self, i: u32) -> Vec<u32> {
+        let (a, b, c) = self.get_face(i);
+        vec![a, b, c]
+    }
+
+    fn get_neighbouring_faces(&self, i: u32) -> Vec<u32> {
+        let (surface, local, offset) = self.locate(i, |s| {s.indices.len() / 3});
+        let faces = surface.lock().get_neighbouring_faces(local);
+        faces.iter().map(|f| {f + offset}).collect()
+    }
+
+    fn get_edge_loop(&self, i: u32) -> Vec<u32> {
+        let (surface, local, offset) = self.locate(i, |s| {s.topology().edges.len()});
+        let edges = surface.lock().get_edge_loop(local);
+        edges.iter().map(|e| {e + offset}).collect()
+    }
+
+    fn translated_verts(&self, t: &Transform) -> Vec<Vec3> {
+        self.surfaces.iter().flat_map(|s| {s.lock().translated_verts(t)}).collect()
+    }
+
+    fn translated_faces(&self, t: &Transform) -> Vec<(Vec3, Vec3, Vec3)> {
+        self.surfaces.iter().flat_map(|s| {s.lock().translated_faces(t)}).collect()
+    }
+
+    fn translated_edges(&self, t: &Transform) -> Vec<(Vec3, Vec3)> {
+        self.surfaces.iter().flat_map(|s| {s.lock().translated_edges(t)}).collect()
+    }
+}
+
+impl Mesh {
+
+    // finds the surface that the i'th vert/edge/face (depending on `count`) is in, giving back the surface, the index
+    // inside it, and how many came before it
+    fn locate(&self, i: u32, count: impl Fn(&Surface) -> usize) -> (Arc<parking_lot::Mutex<Surface>>, u32, u32) {
+        let mut offset = 0;
+        for surface in &self.surfaces {
+            let len = count(&surface.lock()) as u32;
+            if i < offset + len {
+                return (surface.clone(), i - offset, offset);
+            }
+            offset += len;
+        }
+        panic!("Index {} is past the end of the mesh!!", i);
+    }
+
+    fn vert_offset(&self, surface: &Arc<parking_lot::Mutex<Surface>>) -> u32 {
+        self.surfaces.iter().take_while(|s| {!Arc::ptr_eq(s, surface)}).map(|s| {s.lock().verts.len() as u32}).sum()
+    }
+
+    fn face_offset(&self, surface: &Arc<parking_lot::Mutex<Surface>>) -> u32 {
+        self.surfaces.iter().take_while(|s| {!Arc::ptr_eq(s, surface)}).map(|s| {(s.lock().indices.len() / 3) as u32}).sum()
+    }
+}
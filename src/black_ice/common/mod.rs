@@ -4,6 +4,9 @@ pub mod vertex;
 pub mod angles;
 pub mod matrices;
 pub mod mesh;
+pub mod mesh_import;
+pub mod mesh_processing;
+pub mod mesh_topology;
 pub mod transform;
 pub mod filesystem;
 pub mod engine;
@@ -18,8 +21,6 @@ impl Base for Vec<i16>{}
 impl Base for Vec<(i16, Vec3)> {}
 impl Base for String {}
 
-pub const APP_DIR: &str = "F:\\Rust\\Program 1";
-
 pub fn concat_str(a: &str, b: &str) -> String {
 
     let mut aa = a.to_string();
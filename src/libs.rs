@@ -1,6 +1,10 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use black_ice::common::engine::gamesys::*;
+use black_ice::common::engine::asset_mgr::{AssetFolder, AssetPack};
+use black_ice::common::engine::cooker;
+use colored::Colorize;
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
 
@@ -14,4 +18,49 @@ pub mod black_ice {
 pub unsafe fn init_game_env() {
     ENV = Lazy::new( || {Some(Arc::new(Mutex::new(Env::new_sdl())))});
     Env::init();
-}
\ No newline at end of file
+}
+
+/// Builds a `.pkg` asset pack out of an asset folder. This does not need the game environment to be initialised.
+#[no_mangle]
+pub unsafe fn build_asset_pack(folder: String, out: String) -> bool {
+    let asset_folder = match AssetFolder::load(PathBuf::from(folder)) {
+        Ok(asset_folder) => asset_folder,
+        Err(e) => {
+            println!("{} {}", "Failed to read asset folder!!".red(), e);
+            return false;
+        }
+    };
+    match AssetPack::build(&asset_folder, PathBuf::from(out.clone())) {
+        Ok(_) => {
+            println!("Built asset pack {}", out);
+            true
+        },
+        Err(e) => {
+            println!("{} {}", "Failed to build asset pack!!".red(), e);
+            false
+        }
+    }
+}
+
+/// Cooks an asset folder into a `.pkg` asset pack, compiling the shaders and decoding the textures so that the game
+/// doesn't need to at load time. Like `build_asset_pack`, this does not need the game environment to be initialised.
+#[no_mangle]
+pub unsafe fn cook_asset_pack(folder: String, out: String) -> bool {
+    let asset_folder = match AssetFolder::load(PathBuf::from(folder)) {
+        Ok(asset_folder) => asset_folder,
+        Err(e) => {
+            println!("{} {}", "Failed to read asset folder!!".red(), e);
+            return false;
+        }
+    };
+    match cooker::cook_folder(&asset_folder, PathBuf::from(out.clone())) {
+        Ok(_) => {
+            println!("Cooked asset pack {}", out);
+            true
+        },
+        Err(e) => {
+            println!("{} {}", "Failed to cook asset pack!!".red(), e);
+            false
+        }
+    }
+}